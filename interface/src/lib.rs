@@ -0,0 +1,163 @@
+// ============================================================================
+// FLUIDSOL INTERFACE
+// ============================================================================
+//
+// What other programs and off-chain clients need to compose with FluidSOL,
+// without pulling in the program entrypoint:
+//
+// - `cpi`: Anchor CPI helpers (`cpi::deposit_sol(ctx, amount)?.get()` yields
+//   the fSOL minted, `cpi::withdraw_sol` and `cpi::liquid_unstake` the SOL
+//   returned)
+// - `instruction`: plain `Instruction` builders for the user-facing flows
+// - `pda`: address derivation for the pool and its side accounts
+// - account types to deserialize pool, oracle, rate-history and unstake-pool
+//   state
+
+use anchor_lang::prelude::*;
+
+pub use liquid_staking::cpi;
+pub use liquid_staking::program::LiquidStaking;
+pub use liquid_staking::{
+    ExchangeRate, PriceOracle, RateHistory, RateSnapshot, StakingPool, UnstakePool, ValidatorInfo,
+};
+pub use liquid_staking::ID;
+
+/// Account structs for CPI (`AccountInfo`s) and for clients (`Pubkey`s)
+pub mod accounts {
+    pub use liquid_staking::cpi::accounts::{
+        DepositSol, DepositSolWithReferral, GetExchangeRate, LiquidUnstake, WithdrawSol,
+    };
+}
+
+/// Address derivation
+pub mod pda {
+    use super::*;
+
+    /// Pool PDA for an fSOL mint
+    pub fn pool(fluid_sol_mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"pool", fluid_sol_mint.as_ref()], &ID)
+    }
+
+    /// Price oracle of a pool
+    pub fn price_oracle(pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"price_oracle", pool.as_ref()], &ID)
+    }
+
+    /// Exchange-rate history of a pool
+    pub fn rate_history(pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"rate_history", pool.as_ref()], &ID)
+    }
+
+    /// Liquid unstake pool of a pool
+    pub fn unstake_pool(pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"unstake_pool", pool.as_ref()], &ID)
+    }
+
+    /// LP mint of an unstake pool
+    pub fn lp_mint(unstake_pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"lp_mint", unstake_pool.as_ref()], &ID)
+    }
+
+    /// fSOL vault of an unstake pool
+    pub fn unstake_vault(unstake_pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"unstake_vault", unstake_pool.as_ref()], &ID)
+    }
+}
+
+/// Instruction builders
+pub mod instruction {
+    use super::*;
+    use anchor_lang::solana_program::instruction::Instruction;
+    use anchor_lang::{system_program, InstructionData};
+
+    /// Deposit `sol_amount` lamports from `user` for fSOL
+    ///
+    /// `fee_token_account` is only needed when the pool charges a deposit fee.
+    pub fn deposit_sol(
+        user: Pubkey,
+        fluid_sol_mint: Pubkey,
+        user_fluid_sol_account: Pubkey,
+        fee_token_account: Option<Pubkey>,
+        token_program: Pubkey,
+        sol_amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: ID,
+            accounts: liquid_staking::accounts::DepositSol {
+                user,
+                pool: pda::pool(&fluid_sol_mint).0,
+                fluid_sol_mint,
+                user_fluid_sol_account,
+                fee_token_account,
+                system_program: system_program::ID,
+                token_program,
+            }
+            .to_account_metas(None),
+            data: liquid_staking::instruction::DepositSol { sol_amount }.data(),
+        }
+    }
+
+    /// Burn `fluid_sol_amount` fSOL from `user` for SOL out of the liquid reserve
+    pub fn withdraw_sol(
+        user: Pubkey,
+        fluid_sol_mint: Pubkey,
+        user_fluid_sol_account: Pubkey,
+        token_program: Pubkey,
+        fluid_sol_amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: ID,
+            accounts: liquid_staking::accounts::WithdrawSol {
+                user,
+                pool: pda::pool(&fluid_sol_mint).0,
+                fluid_sol_mint,
+                user_fluid_sol_account,
+                system_program: system_program::ID,
+                token_program,
+            }
+            .to_account_metas(None),
+            data: liquid_staking::instruction::WithdrawSol { fluid_sol_amount }.data(),
+        }
+    }
+
+    /// Swap `fluid_sol_amount` fSOL from `user` for SOL out of the pool's
+    /// unstake pool, failing below `min_sol_out`
+    pub fn liquid_unstake(
+        user: Pubkey,
+        fluid_sol_mint: Pubkey,
+        user_fluid_sol_account: Pubkey,
+        token_program: Pubkey,
+        fluid_sol_amount: u64,
+        min_sol_out: u64,
+    ) -> Instruction {
+        let pool = pda::pool(&fluid_sol_mint).0;
+        let unstake_pool = pda::unstake_pool(&pool).0;
+        Instruction {
+            program_id: ID,
+            accounts: liquid_staking::accounts::LiquidUnstake {
+                user,
+                pool,
+                unstake_pool,
+                fluid_sol_mint,
+                user_fluid_sol_account,
+                fluid_sol_vault: pda::unstake_vault(&unstake_pool).0,
+                token_program,
+            }
+            .to_account_metas(None),
+            data: liquid_staking::instruction::LiquidUnstake {
+                fluid_sol_amount,
+                min_sol_out,
+            }
+            .data(),
+        }
+    }
+
+    /// Read a pool's fSOL price (`ExchangeRate` in return data)
+    pub fn get_exchange_rate(pool: Pubkey) -> Instruction {
+        Instruction {
+            program_id: ID,
+            accounts: liquid_staking::accounts::GetExchangeRate { pool }.to_account_metas(None),
+            data: liquid_staking::instruction::GetExchangeRate {}.data(),
+        }
+    }
+}
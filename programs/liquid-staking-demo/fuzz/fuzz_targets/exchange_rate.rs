@@ -0,0 +1,43 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use liquid_staking::math::*;
+
+// (total_sol, supply, amount, rewards, protocol_fee_bps)
+fuzz_target!(|input: (u64, u64, u64, u64, u16)| {
+    let (total_sol, supply, amount, rewards, fee_bps) = input;
+
+    // Deposit -> withdraw never returns more than was put in
+    if let Ok(minted) = fluid_sol_for_deposit(amount, total_sol, supply) {
+        if let (Some(total_after), Some(supply_after)) =
+            (total_sol.checked_add(amount), supply.checked_add(minted))
+        {
+            if let Ok(back) = sol_for_withdrawal(minted, total_after, supply_after) {
+                assert!(back <= amount);
+            }
+        }
+    }
+
+    if let Ok(fee) = instant_withdrawal_fee(amount) {
+        assert!(fee <= amount);
+    }
+
+    if let Ok((user, fee)) = split_rewards(rewards, fee_bps) {
+        assert_eq!(user as u128 + fee as u128, rewards as u128);
+    }
+
+    // The derived rate never claims more SOL than the pool holds...
+    if let Ok(rate) = exchange_rate(total_sol, supply) {
+        assert!(supply as u128 * rate as u128 / RATE_PRECISION as u128 <= total_sol as u128 || supply == 0);
+
+        // ...and never decreases when rewards land
+        if let Ok((user, _)) = split_rewards(rewards, fee_bps.min(10_000)) {
+            if let Some(new_rate) = total_sol
+                .checked_add(user)
+                .and_then(|total| exchange_rate(total, supply).ok())
+            {
+                assert!(new_rate >= rate);
+            }
+        }
+    }
+});
@@ -1,33 +1,50 @@
+// Only for `#[program]`'s generated IDL helpers (crate-root `__private::__idl`),
+// which call the deprecated AccountInfo::realloc and can't take an item-level allow
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{System};
-use anchor_spl::token::Token;
-use anchor_spl::token::{Mint, TokenAccount};
+use anchor_spl::metadata::mpl_token_metadata::types::DataV2;
+use anchor_spl::metadata::Metadata;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use solana_program_option::COption;
 
+pub mod math;
+pub mod migrate;
+pub mod spl_compat;
+pub mod stake;
+pub mod vote;
+
+use math::SafeMath;
+
 declare_id!("4fLrcA8T6sH1z691Rv4JubkzqoNq9fjooaw4iKfjXzj3");
 
 const STAKE_ACCOUNT_SIZE: usize = 200;
+const MAX_FEE_RECIPIENTS: usize = 5;
+const SCORE_HISTORY_LEN: usize = 10;
+const DEFAULT_MAX_VALIDATOR_COMMISSION: u8 = 10;
+const DEFAULT_MAX_RATE_STALENESS_SLOTS: u64 = 432_000; // One epoch, the harvest cadence
+const MAX_BLACKLIST_ENTRIES: usize = 50;
+const POOL_VERSION: u8 = 3;
+const VALIDATOR_VERSION: u8 = 1;
+const RESERVED_SPACE: usize = 64;
+const RATE_HISTORY_LEN: usize = 64;
+const LOCKED_LP_SHARES: u64 = 1_000_000;
+const FLUID_SOL_NAME: &str = "FluidSOL";
+const FLUID_SOL_SYMBOL: &str = "fSOL";
 
 #[program]
 pub mod liquid_staking {
     use super::*;
 
+    /// Create a pool with a classic SPL Token (or Token-2022, without extensions) fSOL mint
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
     ) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
         
-        pool.authority = ctx.accounts.authority.key();
-        pool.total_sol_deposited = 0;
-        pool.total_fluidSOL_minted = 0;
-        pool.exchange_rate = 1_000_000_000; // 1:1 initially
-        pool.staked_sol_balance = 0;        // SOL staked to validators
-        pool.liquid_reserve = 0;            // SOL kept for instant withdrawals
-        pool.protocol_fees_earned = 0;      // Protocol revenue
-        pool.bump = ctx.bumps.pool;
-        pool.validator_count = 0;
-        pool.target_reserve_ratio = 30;     // 30% reserve target
-        pool.protocol_fee_bps = 1000;       // 10% fee in basis points
+        pool.initialize(ctx.accounts.authority.key(), ctx.accounts.fluid_sol_mint.key(), ctx.bumps.pool, &Clock::get()?);
         
         msg!("FluidSOL liquid staking pool initialized!");
         msg!("Pool authority: {}", pool.authority);
@@ -36,6 +53,128 @@ pub mod liquid_staking {
         Ok(())
     }
 
+    /// Create a pool whose fSOL mint is a Token-2022 mint carrying its own metadata
+    ///
+    /// The metadata-pointer extension points the mint at itself and the
+    /// token-metadata extension stores name "FluidSOL", symbol "fSOL" and `uri`,
+    /// with the pool PDA as update authority.
+    pub fn initialize_pool_with_metadata(
+        ctx: Context<InitializePoolWithMetadata>,
+        uri: String,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.initialize(ctx.accounts.authority.key(), ctx.accounts.fluid_sol_mint.key(), ctx.bumps.pool, &Clock::get()?);
+
+        // Token-2022 reallocs the mint for the metadata; it must already hold the rent
+        let mint_info = ctx.accounts.fluid_sol_mint.to_account_info();
+        let metadata_len = token_metadata_len(FLUID_SOL_NAME, FLUID_SOL_SYMBOL, &uri);
+        let rent_due = Rent::get()?
+            .minimum_balance(mint_info.data_len() + metadata_len)
+            .saturating_sub(mint_info.lamports());
+        if rent_due > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: mint_info.clone(),
+                    },
+                ),
+                rent_due,
+            )?;
+        }
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        anchor_spl::token_interface::token_metadata_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::TokenMetadataInitialize {
+                    program_id: ctx.accounts.token_program.to_account_info(),
+                    metadata: mint_info.clone(),
+                    update_authority: pool.to_account_info(),
+                    mint_authority: pool.to_account_info(),
+                    mint: mint_info,
+                },
+                pool_signer,
+            ),
+            FLUID_SOL_NAME.to_string(),
+            FLUID_SOL_SYMBOL.to_string(),
+            uri,
+        )?;
+        
+        msg!("FluidSOL liquid staking pool initialized with Token-2022 metadata!");
+        msg!("Pool authority: {}", pool.authority);
+        msg!("Target reserve ratio: {}%", pool.target_reserve_ratio);
+        
+        Ok(())
+    }
+
+    /// Create the Metaplex metadata account for a classic SPL Token fSOL mint
+    pub fn create_token_metadata(
+        ctx: Context<CreateTokenMetadata>,
+        uri: String,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        anchor_spl::metadata::create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                anchor_spl::metadata::CreateMetadataAccountsV3 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    mint: ctx.accounts.fluid_sol_mint.to_account_info(),
+                    mint_authority: pool.to_account_info(),
+                    payer: ctx.accounts.authority.to_account_info(),
+                    update_authority: pool.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+                pool_signer,
+            ),
+            fluid_sol_metadata(uri),
+            true,
+            true,
+            None,
+        )?;
+
+        msg!("📛 fSOL metadata created: {}", ctx.accounts.metadata.key());
+
+        Ok(())
+    }
+
+    /// Point the Metaplex metadata of the fSOL mint at a new URI
+    pub fn update_token_metadata(
+        ctx: Context<UpdateTokenMetadata>,
+        uri: String,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        anchor_spl::metadata::update_metadata_accounts_v2(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                anchor_spl::metadata::UpdateMetadataAccountsV2 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    update_authority: pool.to_account_info(),
+                },
+                pool_signer,
+            ),
+            None,
+            Some(fluid_sol_metadata(uri)),
+            None,
+            None,
+        )?;
+
+        msg!("📛 fSOL metadata updated: {}", ctx.accounts.metadata.key());
+
+        Ok(())
+    }
+
     /// Add a validator to the pool's delegation strategy
     pub fn add_validator(
         ctx: Context<AddValidator>,
@@ -49,6 +188,19 @@ pub mod liquid_staking {
         require!(allocation_percentage <= 100, ErrorCode::InvalidAllocation);
         require!(pool.validator_count < 10, ErrorCode::TooManyValidators); // Max 10 validators
         
+        let blacklist = &mut ctx.accounts.blacklist;
+        if blacklist.pool == Pubkey::default() {
+            blacklist.pool = pool.key();
+            blacklist.bump = ctx.bumps.blacklist;
+        }
+        require!(!blacklist.vote_accounts.contains(&validator_vote_account), ErrorCode::ValidatorBlacklisted);
+        
+        // Admission criteria, judged on the last completed epoch
+        let vote_summary = vote::read_vote_summary(&ctx.accounts.vote_account)?;
+        let epoch_credits = vote_summary.credits_in_epoch(Clock::get()?.epoch.saturating_sub(1));
+        require!(vote_summary.commission <= pool.admission_max_commission, ErrorCode::CommissionTooHigh);
+        require!(epoch_credits >= pool.admission_min_epoch_credits, ErrorCode::InsufficientEpochCredits);
+        
         let validator_info = &mut ctx.accounts.validator_info;
         validator_info.vote_account = validator_vote_account;
         validator_info.allocation_percentage = allocation_percentage;
@@ -56,6 +208,24 @@ pub mod liquid_staking {
         validator_info.last_update_epoch = Clock::get()?.epoch;
         validator_info.performance_score = 100; // Start with perfect score
         validator_info.is_active = true;
+        validator_info.next_stake_index = 0;
+        validator_info.activating_stake = 0;
+        validator_info.deactivating_stake = 0;
+        validator_info.transient_settle_epoch = 0;
+        validator_info.commission = vote_summary.commission;
+        validator_info.commission_changed = false;
+        validator_info.epoch_credits = 0;
+        validator_info.last_vote_slot = 0;
+        validator_info.last_scored_epoch = 0;
+        validator_info.score_history = [0; SCORE_HISTORY_LEN];
+        validator_info.admission_commission = vote_summary.commission;
+        validator_info.quarantine_epoch = 0;
+        validator_info.target_allocation_bps = (allocation_percentage as u16) * 100;
+        validator_info.allocation_pinned = false;
+        validator_info.concentration_tag = 0;
+        validator_info.pool = pool.key();
+        validator_info.version = VALIDATOR_VERSION;
+        validator_info.reserved = [0; RESERVED_SPACE];
         
         pool.validator_count += 1;
         
@@ -66,166 +236,103 @@ pub mod liquid_staking {
     }
 
     /// Deposit SOL and receive FluidSOL tokens
+    ///
+    /// Returns the fSOL minted to the depositor (return data, for CPI callers).
     pub fn deposit_sol(
         ctx: Context<DepositSol>,
         sol_amount: u64,
-    ) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        
-        require!(sol_amount > 0, ErrorCode::InvalidAmount);
-        require!(sol_amount >= 1_000_000, ErrorCode::MinimumDeposit); // 0.001 SOL minimum
-        
-        // Calculate FluidSOL tokens to mint
-        let fluidSOL_to_mint = sol_amount
-            .checked_mul(1_000_000_000)
-            .unwrap()
-            .checked_div(pool.exchange_rate)
-            .unwrap();
-        
-        msg!("Depositing {} SOL for {} fSOL", 
-             sol_amount as f64 / 1_000_000_000.0,
-             fluidSOL_to_mint as f64 / 1_000_000_000.0);
-
-        // Transfer SOL from user to pool
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.user.to_account_info(),
-                to: pool.to_account_info(),
-            },
-        );
-        anchor_lang::system_program::transfer(cpi_context, sol_amount)?;
-
-        // Mint FluidSOL tokens to user
-        let seeds = &[b"pool".as_ref(), &[pool.bump]];
-        let signer = &[&seeds[..]];
+    ) -> Result<u64> {
+        let (user_fluid_sol, _) = ctx.accounts.process_deposit(sol_amount, None)?;
+        Ok(user_fluid_sol)
+    }
 
-        let cpi_accounts = anchor_spl::token::MintTo {
-        mint: ctx.accounts.fluidSOL_mint.to_account_info(),
-        to: ctx.accounts.user_fluidSOL_account.to_account_info(),
-        authority: pool.to_account_info(),
-    };
-    let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        cpi_accounts,
-        signer,
-    );
-        anchor_spl::token::mint_to(cpi_ctx, fluidSOL_to_mint)?;
+    /// Deposit SOL with a referrer, who receives part of the deposit fee in fSOL
+    ///
+    /// Returns the fSOL minted to the depositor.
+    pub fn deposit_sol_with_referral(
+        ctx: Context<DepositSolWithReferral>,
+        sol_amount: u64,
+    ) -> Result<u64> {
+        let referrer = ctx.accounts.referrer_fluid_sol_account.to_account_info();
+        let (user_fluid_sol, referral_fee) = ctx.accounts.deposit.process_deposit(sol_amount, Some(referrer))?;
 
-        // Update pool state
-        pool.total_sol_deposited = pool.total_sol_deposited.checked_add(sol_amount).unwrap();
-        pool.total_fluidSOL_minted = pool.total_fluidSOL_minted.checked_add(fluidSOL_to_mint).unwrap();
-        
-        // Add to liquid reserve initially (will be rebalanced later)
-        pool.liquid_reserve = pool.liquid_reserve.checked_add(sol_amount).unwrap();
+        let referral_record = &mut ctx.accounts.referral_record;
+        if referral_record.referral_count == 0 {
+            referral_record.pool = ctx.accounts.deposit.pool.key();
+            referral_record.referrer = ctx.accounts.referrer_fluid_sol_account.key();
+            referral_record.bump = ctx.bumps.referral_record;
+        }
+        referral_record.referral_count = referral_record.referral_count.safe_add(1)?;
+        referral_record.total_sol_referred = referral_record.total_sol_referred.safe_add(sol_amount)?;
+        referral_record.total_fees_earned = referral_record.total_fees_earned.safe_add(referral_fee)?;
 
-        msg!("Deposit successful! Pool balance: {} SOL", 
-             pool.total_sol_deposited as f64 / 1_000_000_000.0);
+        msg!("Referrer {} earned {} fSOL", 
+             referral_record.referrer,
+             referral_fee as f64 / 1_000_000_000.0);
 
-        Ok(())
+        Ok(user_fluid_sol)
     }
 
     /// Withdraw SOL by burning FluidSOL tokens (instant if reserve available)
+    ///
+    /// Returns the SOL sent to the user after the instant withdrawal fee.
     pub fn withdraw_sol(
         ctx: Context<WithdrawSol>,
-        fluidSOL_amount: u64,
-    ) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        
-        // Validate withdrawal amount
-        require!(fluidSOL_amount > 0, ErrorCode::InvalidAmount);
-        
-        // Calculate SOL to return based on current exchange rate
-        let sol_to_return = fluidSOL_amount
-            .checked_mul(pool.exchange_rate)
-            .unwrap()
-            .checked_div(1_000_000_000)
-            .unwrap();
-        
-        // Check if we have enough in liquid reserve for instant withdrawal
-        require!(sol_to_return <= pool.liquid_reserve, ErrorCode::InsufficientLiquidity);
-        
-        // Calculate 0.3% instant withdrawal fee
-        let withdrawal_fee = sol_to_return.checked_mul(30).unwrap().checked_div(10000).unwrap();
-        let net_sol_to_user = sol_to_return.checked_sub(withdrawal_fee).unwrap();
-        
-        msg!("Withdrawing {} fSOL for {} SOL (fee: {} SOL)", 
-            fluidSOL_amount as f64 / 1_000_000_000.0,
-            net_sol_to_user as f64 / 1_000_000_000.0,
-            withdrawal_fee as f64 / 1_000_000_000.0);
-
-        // Burn FluidSOL tokens from user's account
-        let cpi_accounts = anchor_spl::token::Burn {
-            mint: ctx.accounts.fluidSOL_mint.to_account_info(),
-            from: ctx.accounts.user_fluidSOL_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-        );
-        anchor_spl::token::burn(cpi_ctx, fluidSOL_amount)?;
-
-        // Transfer SOL from pool to user (direct lamport manipulation - pool has data)
-        **pool.to_account_info().try_borrow_mut_lamports()? -= net_sol_to_user;
-        **ctx.accounts.user.to_account_info().try_borrow_mut_lamports()? += net_sol_to_user;
-
-        // Update pool accounting
-        pool.total_sol_deposited = pool.total_sol_deposited.checked_sub(sol_to_return).unwrap();
-        pool.total_fluidSOL_minted = pool.total_fluidSOL_minted.checked_sub(fluidSOL_amount).unwrap();
-        pool.liquid_reserve = pool.liquid_reserve.checked_sub(sol_to_return).unwrap();
-        pool.protocol_fees_earned = pool.protocol_fees_earned.checked_add(withdrawal_fee).unwrap();
-
-        msg!("Withdrawal successful! Remaining pool reserve: {} SOL", 
-            pool.liquid_reserve as f64 / 1_000_000_000.0);
-
-        Ok(())
+        fluid_sol_amount: u64,
+    ) -> Result<u64> {
+        let accounts = ctx.accounts;
+        let user = accounts.user.to_account_info();
+        withdraw_from_reserve(
+            &mut accounts.pool,
+            &accounts.fluid_sol_mint,
+            &accounts.user_fluid_sol_account,
+            &user,
+            &accounts.token_program,
+            fluid_sol_amount,
+            &user,
+        )
     }
 
+    /// Stake SOL from the liquid reserve into a new pool stake account
+    ///
+    /// Stake accounts live at `[b"stake", pool, vote_account, index]` where
+    /// index counts up from 0 per validator (`ValidatorInfo.next_stake_index`),
+    /// so every delegation the pool owns can be enumerated from on-chain state.
+    /// Tagged validators under a tag cap need every ValidatorInfo in index
+    /// order as remaining accounts (see `check_concentration`).
     pub fn stake_to_validator(
         ctx: Context<StakeToValidator>,
         amount: u64,
-        slot: u64,
     ) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
 
-        msg!("🔍 STEP 0: Starting stake_to_validator");
-        msg!("🔍 Pool liquid reserve: {}", pool.liquid_reserve);
-        msg!("🔍 Stake amount requested: {}", amount);
-
         // Authority and validation checks
         require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
         require!(amount <= pool.liquid_reserve, ErrorCode::InsufficientLiquidity);
-        // require!(validator_index < pool.validator_count, ErrorCode::InvalidValidatorIndex);
-        require!(slot > 0, ErrorCode::InvalidValidatorIndex);
         
         let validator_info = &mut ctx.accounts.validator_info;
         require!(validator_info.is_active, ErrorCode::ValidatorInactive);
-
-        // Calculate rent-exempt minimum (stake account already has rent from init)
-        let rent = Rent::get()?;
-        let stake_account_rent = rent.minimum_balance(STAKE_ACCOUNT_SIZE);
-
-        msg!("🔍 STEP 1: Stake account created by Anchor");
-        msg!("🔍 Stake space: {}", STAKE_ACCOUNT_SIZE);
-        msg!("🔍 Rent in account: {}", stake_account_rent);
-        msg!("🔍 Stake account (PDA): {}", ctx.accounts.stake_account.key());
+        if pool.allocation_mode == AllocationMode::PerformanceWeighted {
+            let target = validator_info.target_delegation(pool.staked_sol_balance.safe_add(amount)?)?;
+            require!(
+                validator_info.total_delegated.safe_add(amount)? <= target,
+                ErrorCode::AboveTargetAllocation
+            );
+        }
 
         // Pool authority seeds for signing
-        let pool_seeds = &[b"pool".as_ref(), &[pool.bump]];
-        
+        let pool_seeds = pool.signer_seeds();
         let pool_signer = &[&pool_seeds[..]];
 
-        // STEP 1: Initialize stake account (Anchor already created it as system account)
-        msg!("🔍 STEP 1: Initializing stake account...");
-        let authorized = anchor_lang::solana_program::stake::state::Authorized {
+        // Initialize the stake account (Anchor already created it, authority paid the rent)
+        let authorized = solana_stake_interface::state::Authorized {
             staker: pool.key(),
             withdrawer: pool.key(),
         };
-        let initialize_ix = anchor_lang::solana_program::stake::instruction::initialize(
+        let initialize_ix = solana_stake_interface::instruction::initialize(
             &ctx.accounts.stake_account.key(),
             &authorized,
-            &anchor_lang::solana_program::stake::state::Lockup::default(),
+            &solana_stake_interface::state::Lockup::default(),
         );
         anchor_lang::solana_program::program::invoke(
             &initialize_ix,
@@ -234,67 +341,16 @@ pub mod liquid_staking {
                 ctx.accounts.rent.to_account_info(),
             ],
         )?;
-        msg!("✅ STEP 1 SUCCESS: Stake account initialized!");
-
-        // STEP 2: Transfer staking amount from pool to stake account
-        msg!("🔍 STEP 2: Transferring {} lamports from pool to stake account...", amount);
 
         // Direct lamport transfer - pool has data so can't use system program
-        **pool.to_account_info().try_borrow_mut_lamports()? -= amount;
-        **ctx.accounts.stake_account.to_account_info().try_borrow_mut_lamports()? += amount;
-
-        msg!("✅ STEP 2 SUCCESS: Amount transferred to stake account!");
-
-        msg!("💎 BALANCES AFTER TRANSFER:");
-        msg!("  pool balance: {}", pool.to_account_info().lamports());
-        msg!("  stake_account balance: {}", ctx.accounts.stake_account.to_account_info().lamports());
-        msg!("  stake_account_rent_requirement: {}", stake_account_rent);
-
-        // STEP 3: Delegate stake to validator
-        msg!("🔍 STEP 3: Delegating stake to validator...");
-        // TODO - Fix: Incorrect Program ID for instruction here somewhere.
-        // msg! logs these accounts below.!
-        msg!("💎 Before DELEGATE IX 1 {}", &ctx.accounts.stake_account.key());
-        msg!("💎 Before DELEGATE IX 2 {}", &pool.key());
-        msg!("💎 Before DELEGATE IX 3 {}", &ctx.accounts.validator_vote_account.key());
-        let delegate_ix = anchor_lang::solana_program::stake::instruction::delegate_stake(
+        transfer_lamports(&pool.to_account_info(), &ctx.accounts.stake_account.to_account_info(), amount)?;
+
+        // Delegate to the validator, the pool signs as staker
+        let delegate_ix = solana_stake_interface::instruction::delegate_stake(
             &ctx.accounts.stake_account.key(),
-            &pool.key(), // Pool is the staker authority
+            &pool.key(),
             &ctx.accounts.validator_vote_account.key(),
         );
-
-        // Log instruction details
-        msg!("💎 DELEGATE IX CREATED:");
-        msg!("  program_id: {}", delegate_ix.program_id);
-        msg!("  accounts_len: {}", delegate_ix.accounts.len());
-
-        // Log accounts being passed to invoke_signed
-        msg!("💎 INVOKE_SIGNED ACCOUNTS:");
-        msg!("  [0] stake_account: {} (owner: {})", 
-            ctx.accounts.stake_account.key(), 
-            ctx.accounts.stake_account.owner);
-        msg!("  [1] vote_account: {}", ctx.accounts.validator_vote_account.key());
-        msg!("  [2] clock: {}", ctx.accounts.clock.key());
-        msg!("  [3] stake_history: {}", ctx.accounts.stake_history.key());
-        msg!("  [4] stake_config: {}", ctx.accounts.stake_config.key());
-        msg!("  [5] pool (authority): {}", pool.key());
-
-        // Log signer seeds
-        msg!("💎 SIGNER SEEDS:");
-        msg!("  pool_bump: {}", pool.bump);
-
-        msg!("🔍 TESTING PDA DERIVATION:");
-        let (derived_pool, derived_bump) = Pubkey::find_program_address(
-            &[b"pool"], 
-            &crate::ID
-        );
-        msg!("  derived_pool: {}", derived_pool);
-        msg!("  actual_pool: {}", pool.key());
-        msg!("  derived_bump: {}", derived_bump);
-        msg!("  stored_bump: {}", pool.bump);
-
-
-        // CPI - Cross-Program Invocation - signs "on behalf of someone, like PDA"
         anchor_lang::solana_program::program::invoke_signed(
             &delegate_ix,
             &[
@@ -303,26 +359,52 @@ pub mod liquid_staking {
                 ctx.accounts.clock.to_account_info(),
                 ctx.accounts.stake_history.to_account_info(),
                 ctx.accounts.stake_config.to_account_info(),
-                pool.to_account_info(), // Pool signs as staker
+                pool.to_account_info(),
             ],
             pool_signer,
         )?;
-        msg!("✅ STEP 3 SUCCESS: Stake delegated to validator!");
 
-        // STEP 4: Update accounting
-        msg!("🔍 STEP 4: Updating pool accounting...");
+
         // Only subtract the staking amount, not rent (authority already paid rent)
-        pool.liquid_reserve = pool.liquid_reserve.checked_sub(amount).unwrap();
-        pool.staked_sol_balance = pool.staked_sol_balance.checked_add(amount).unwrap();
-        validator_info.total_delegated = validator_info.total_delegated.checked_add(amount).unwrap();
+        pool.liquid_reserve = pool.liquid_reserve.safe_sub(amount)?;
+        pool.staked_sol_balance = pool.staked_sol_balance.safe_add(amount)?;
+        validator_info.total_delegated = validator_info.total_delegated.safe_add(amount)?;
         validator_info.last_update_epoch = Clock::get()?.epoch;
+        check_concentration(
+            pool,
+            validator_info,
+            &[(validator_info.key(), validator_info.total_delegated)],
+            ctx.remaining_accounts,
+        )?;
+        let stake_record = &mut ctx.accounts.stake_record;
+        stake_record.pool = pool.key();
+        stake_record.validator = validator_info.key();
+        stake_record.stake_account = ctx.accounts.stake_account.key();
+        stake_record.seed_index = validator_info.next_stake_index;
+        stake_record.principal = amount;
+        stake_record.activation_epoch = validator_info.last_update_epoch;
+        stake_record.deactivation_epoch = u64::MAX;
+        stake_record.last_observed_lamports = amount;
+        stake_record.state = StakeAccountState::Activating;
+        stake_record.bump = ctx.bumps.stake_record;
+        stake::track_transient(pool, validator_info, stake_record)?;
+        pool.stake_account_count = pool.stake_account_count.safe_add(1)?;
 
-        msg!("✅ VALÓDI STAKING SUCCESSFUL! {} SOL delegated!", amount as f64 / 1_000_000_000.0);
+        validator_info.next_stake_index = validator_info.next_stake_index.safe_add(1)?;
+
+        msg!("Delegated {} SOL to validator {} via stake account {}",
+             amount as f64 / 1_000_000_000.0,
+             validator_info.vote_account,
+             ctx.accounts.stake_account.key());
         
         Ok(())
     }
 
-    /// 🔥 NEW: Harvest rewards from specific validator
+    /// Harvest rewards (or book slashing) for one pool stake account
+    ///
+    /// Compares the stake account's lamports above its rent reserve against
+    /// what its PoolStakeAccount record last observed, so each stake account is
+    /// accounted for separately and aggregated into ValidatorInfo.
     pub fn harvest_rewards(
         ctx: Context<HarvestRewards>,
         validator_index: u8,
@@ -333,390 +415,3466 @@ pub mod liquid_staking {
         require!(validator_index < pool.validator_count, ErrorCode::InvalidValidatorIndex);
         
         let validator_info = &mut ctx.accounts.validator_info;
-        let stake_account_balance = ctx.accounts.stake_account.to_account_info().lamports();
+        let stake_record = &mut ctx.accounts.stake_record;
+        let stake_account = &ctx.accounts.stake_account;
+        let clock = Clock::get()?;
+        let epoch = clock.epoch;
+
+        let meta = stake::pool_stake_meta(stake_account, &pool.key())?;
+        let observed = stake_account.lamports().safe_sub(meta.rent_exempt_reserve)?;
+        stake::untrack_transient(pool, validator_info, stake_record)?;
+        stake::refresh_stake_record(stake_record, &stake::read_stake_state(stake_account)?, epoch);
         
-        msg!("🌾 Checking rewards for validator {}", validator_index);
+        msg!("🌾 Checking rewards for validator {} stake account {}", validator_index, stake_account.key());
         
-        // Calculate rewards (current balance - original delegation)
-        if stake_account_balance > validator_info.total_delegated {
-            let rewards_earned = stake_account_balance.checked_sub(validator_info.total_delegated).unwrap();
+        // Calculate rewards (current balance - last observed balance)
+        if observed > stake_record.last_observed_lamports {
+            let rewards_earned = observed.safe_sub(stake_record.last_observed_lamports)?;
             
             msg!("🎉 Found {} SOL rewards from validator!", rewards_earned as f64 / 1_000_000_000.0);
             
-            // Calculate protocol fee (10%)
-            let protocol_fee = rewards_earned
-                .checked_mul(pool.protocol_fee_bps as u64)
-                .unwrap()
-                .checked_div(10000)
-                .unwrap();
-            
-            let user_rewards = rewards_earned.checked_sub(protocol_fee).unwrap();
-            
-            // Update pool accounting
-            pool.staked_sol_balance = pool.staked_sol_balance.checked_add(user_rewards).unwrap();
-            pool.protocol_fees_earned = pool.protocol_fees_earned.checked_add(protocol_fee).unwrap();
-            pool.total_sol_deposited = pool.total_sol_deposited.checked_add(user_rewards).unwrap();
-            
-            // Update exchange rate - FluidSOL now worth more!
-            if pool.total_fluidSOL_minted > 0 {
-                pool.exchange_rate = pool.total_sol_deposited
-                    .checked_mul(1_000_000_000)
-                    .unwrap()
-                    .checked_div(pool.total_fluidSOL_minted)
-                    .unwrap();
+            // Update pool accounting and exchange rate - FluidSOL now worth more!
+            let (protocol_fee, fee_shares) = pool.apply_rewards(rewards_earned, &clock)?;
+            if !pool.mint_fees_as_fluid_sol {
+                // The fee stays in the stake account until it is withdrawn
+                stake_record.fee_lamports = stake_record.fee_lamports.safe_add(protocol_fee)?;
             }
+            mint_fee_shares(
+                pool,
+                &ctx.accounts.fluid_sol_mint,
+                &ctx.accounts.fee_token_account,
+                &ctx.accounts.token_program,
+                fee_shares,
+            )?;
             
             // Update validator tracking
-            validator_info.total_delegated = stake_account_balance;
-            validator_info.last_update_epoch = Clock::get()?.epoch;
+            validator_info.total_delegated = validator_info.total_delegated.safe_add(rewards_earned)?;
             
             msg!("💎 New exchange rate: {}", pool.exchange_rate as f64 / 1_000_000_000.0);
             msg!("🎯 Protocol earned {} SOL", protocol_fee as f64 / 1_000_000_000.0);
             
+        } else if observed < stake_record.last_observed_lamports {
+            let slashed = stake_record.last_observed_lamports.safe_sub(observed)?;
+            
+            msg!("🔻 Stake account lost {} SOL (slashing)", slashed as f64 / 1_000_000_000.0);
+            
+            // Loss is socialized across fSOL holders through the exchange rate
+            pool.apply_slashing(slashed, &clock)?;
+            validator_info.total_delegated = validator_info.total_delegated.safe_sub(slashed)?;
+            
+            msg!("💎 New exchange rate: {}", pool.exchange_rate as f64 / 1_000_000_000.0);
         } else {
-            msg!("⏳ No new rewards from this validator yet");
+            msg!("⏳ No new rewards from this stake account yet");
+        }
+
+        stake_record.last_observed_lamports = observed;
+        stake::track_transient(pool, validator_info, stake_record)?;
+        validator_info.last_update_epoch = epoch;
+
+        if let Some(rate_history) = ctx.accounts.rate_history.as_mut() {
+            rate_history.record(epoch, pool);
+        }
+        if let Some(price_oracle) = ctx.accounts.price_oracle.as_mut() {
+            price_oracle.update(pool);
         }
         
         Ok(())
     }
 
-    /// Update rewards from validators and adjust exchange rate
-    pub fn update_rewards(
-        ctx: Context<UpdateRewards>,
-        total_rewards_earned: u64,
+    /// Begin unstaking a pool stake account
+    ///
+    /// Authority only while the validator is active; anyone may unstake from
+    /// an inactive (e.g. quarantined) validator.
+    pub fn deactivate_stake_account(
+        ctx: Context<DeactivateStakeAccount>,
     ) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
         
-        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
-        require!(total_rewards_earned > 0, ErrorCode::InvalidAmount);
+        require!(
+            ctx.accounts.authority.key() == pool.authority || !ctx.accounts.validator_info.is_active,
+            ErrorCode::Unauthorized
+        );
         
-        // Calculate protocol fee (10% of rewards)
-        let protocol_fee = total_rewards_earned
-            .checked_mul(pool.protocol_fee_bps as u64)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap();
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+
+        stake::deactivate(
+            &ctx.accounts.stake_account,
+            &ctx.accounts.clock.to_account_info(),
+            &pool.to_account_info(),
+            pool_signer,
+        )?;
+
+        let validator_info = &mut ctx.accounts.validator_info;
+        let stake_record = &mut ctx.accounts.stake_record;
+        stake::untrack_transient(pool, validator_info, stake_record)?;
+        stake_record.deactivation_epoch = ctx.accounts.clock.epoch;
+        stake_record.state = StakeAccountState::Deactivating;
+        stake::track_transient(pool, validator_info, stake_record)?;
         
-        let user_rewards = total_rewards_earned.checked_sub(protocol_fee).unwrap();
+        msg!("Deactivating stake account {} ({} SOL)", 
+             stake_record.stake_account,
+             stake_record.last_observed_lamports as f64 / 1_000_000_000.0);
         
-        // Add rewards to pool (90% to users via exchange rate, 10% to protocol)
-        pool.staked_sol_balance = pool.staked_sol_balance.checked_add(user_rewards).unwrap();
-        pool.protocol_fees_earned = pool.protocol_fees_earned.checked_add(protocol_fee).unwrap();
-        pool.total_sol_deposited = pool.total_sol_deposited.checked_add(user_rewards).unwrap();
+        Ok(())
+    }
+
+    /// Withdraw a fully deactivated stake account back into the liquid reserve (authority only)
+    ///
+    /// Rewards/slashing must be harvested first so the record matches the
+    /// account. The rent reserve goes back to the authority who paid it and
+    /// the stake record is closed.
+    pub fn withdraw_stake_account(
+        ctx: Context<WithdrawStakeAccount>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
         
-        // Update exchange rate - more SOL backing same FluidSOL tokens
-        if pool.total_fluidSOL_minted > 0 {
-            pool.exchange_rate = pool.total_sol_deposited
-                .checked_mul(1_000_000_000)
-                .unwrap()
-                .checked_div(pool.total_fluidSOL_minted)
-                .unwrap();
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let stake_account = &ctx.accounts.stake_account;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let stake_record = &mut ctx.accounts.stake_record;
+        let epoch = ctx.accounts.clock.epoch;
+
+        let meta = stake::pool_stake_meta(stake_account, &pool.key())?;
+        stake::untrack_transient(pool, validator_info, stake_record)?;
+        stake::refresh_stake_record(stake_record, &stake::read_stake_state(stake_account)?, epoch);
+        require!(stake_record.state == StakeAccountState::Inactive, ErrorCode::StakeNotInactive);
+
+        let withdrawable = stake_account.lamports().safe_sub(meta.rent_exempt_reserve)?;
+        require!(withdrawable == stake_record.last_observed_lamports, ErrorCode::UnharvestedRewards);
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+
+        // Principal + harvested rewards to the pool, rent back to the authority
+        for (recipient, lamports) in [
+            (pool.to_account_info(), withdrawable),
+            (ctx.accounts.authority.to_account_info(), meta.rent_exempt_reserve),
+        ] {
+            let withdraw_ix = solana_stake_interface::instruction::withdraw(
+                &stake_account.key(),
+                &pool.key(),
+                &recipient.key(),
+                lamports,
+                None,
+            );
+            anchor_lang::solana_program::program::invoke_signed(
+                &withdraw_ix,
+                &[
+                    stake_account.to_account_info(),
+                    recipient,
+                    ctx.accounts.clock.to_account_info(),
+                    ctx.accounts.stake_history.to_account_info(),
+                    pool.to_account_info(),
+                ],
+                pool_signer,
+            )?;
         }
+
+        // Harvested lamport-mode fees are already in protocol_fees_earned
+        let staked = stake_record.staked_lamports()?;
+        pool.staked_sol_balance = pool.staked_sol_balance.safe_sub(staked)?;
+        pool.liquid_reserve = pool.liquid_reserve.safe_add(staked)?;
+        pool.stake_account_count = pool.stake_account_count.safe_sub(1)?;
+        validator_info.total_delegated = validator_info.total_delegated.safe_sub(withdrawable)?;
+        validator_info.last_update_epoch = epoch;
         
-        msg!("Rewards updated: {} SOL total, {} SOL to users, {} SOL protocol fee", 
-             total_rewards_earned as f64 / 1_000_000_000.0,
-             user_rewards as f64 / 1_000_000_000.0,
-             protocol_fee as f64 / 1_000_000_000.0);
-        msg!("New exchange rate: {}", pool.exchange_rate as f64 / 1_000_000_000.0);
+        msg!("Withdrew {} SOL from stake account {} into the reserve", 
+             withdrawable as f64 / 1_000_000_000.0,
+             stake_account.key());
         
         Ok(())
     }
 
-    /// Rebalance pool to maintain target reserve ratio
-    pub fn rebalance_pool(
-        ctx: Context<RebalancePool>,
+    /// Merge two stake accounts of the same validator (authority only)
+    ///
+    /// The source account is closed by the stake program and all of its
+    /// lamports, including the rent reserve the authority paid, land in the
+    /// destination. That reclaimed rent becomes pool backing.
+    pub fn merge_stake_accounts(
+        ctx: Context<MergeStakeAccounts>,
     ) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
         
         require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
         
-        let total_balance = pool.liquid_reserve.checked_add(pool.staked_sol_balance).unwrap();
-        let current_reserve_ratio = if total_balance > 0 {
-            pool.liquid_reserve.checked_mul(100).unwrap().checked_div(total_balance).unwrap()
-        } else {
-            0
-        };
+        let epoch = ctx.accounts.clock.epoch;
+        let destination = &ctx.accounts.destination_stake;
+        let source = &ctx.accounts.source_stake;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let destination_record = &mut ctx.accounts.destination_record;
+        let source_record = &mut ctx.accounts.source_record;
+
+        // Both accounts must be harvested so the merged record stays exact
+        let destination_meta = stake::pool_stake_meta(destination, &pool.key())?;
+        let source_meta = stake::pool_stake_meta(source, &pool.key())?;
+        stake::untrack_transient(pool, validator_info, destination_record)?;
+        stake::untrack_transient(pool, validator_info, source_record)?;
+        stake::refresh_stake_record(destination_record, &stake::read_stake_state(destination)?, epoch);
+        stake::refresh_stake_record(source_record, &stake::read_stake_state(source)?, epoch);
+        require!(
+            destination.lamports().safe_sub(destination_meta.rent_exempt_reserve)? == destination_record.last_observed_lamports
+                && source.lamports().safe_sub(source_meta.rent_exempt_reserve)? == source_record.last_observed_lamports,
+            ErrorCode::UnharvestedRewards
+        );
+
+        // Same state (and same activation epoch while activating); the stake
+        // program enforces the finer rules such as matching credits observed
+        require!(
+            destination_record.state == source_record.state
+                && (destination_record.state != StakeAccountState::Activating
+                    || destination_record.activation_epoch == source_record.activation_epoch),
+            ErrorCode::IncompatibleStakeAccounts
+        );
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+
+        stake::merge(
+            &destination.to_account_info(),
+            &source.to_account_info(),
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.stake_history.to_account_info(),
+            &pool.to_account_info(),
+            pool_signer,
+        )?;
+
+        let reclaimed_rent = source_meta.rent_exempt_reserve;
+        destination_record.principal = destination_record
+            .principal
+            .safe_add(source_record.principal)?
+            .safe_add(reclaimed_rent)?;
+        destination_record.last_observed_lamports = destination_record
+            .last_observed_lamports
+            .safe_add(source_record.last_observed_lamports)?
+            .safe_add(reclaimed_rent)?;
+        destination_record.fee_lamports = destination_record.fee_lamports.safe_add(source_record.fee_lamports)?;
+        stake::track_transient(pool, validator_info, destination_record)?;
+
+        // Reclaimed rent was paid by the authority, it now backs fSOL
+        pool.staked_sol_balance = pool.staked_sol_balance.safe_add(reclaimed_rent)?;
+        pool.total_sol_deposited = pool.total_sol_deposited.safe_add(reclaimed_rent)?;
+        pool.stake_account_count = pool.stake_account_count.safe_sub(1)?;
+        pool.refresh_exchange_rate(&ctx.accounts.clock)?;
+        validator_info.total_delegated = validator_info.total_delegated.safe_add(reclaimed_rent)?;
+        validator_info.last_update_epoch = epoch;
         
-        let target_reserve = total_balance
-            .checked_mul(pool.target_reserve_ratio as u64)
-            .unwrap()
-            .checked_div(100)
-            .unwrap();
+        msg!("Merged {} into {} (reclaimed {} SOL rent)", 
+             source.key(),
+             destination.key(),
+             reclaimed_rent as f64 / 1_000_000_000.0);
         
-        msg!("Current reserve ratio: {}%, target: {}%", 
-             current_reserve_ratio, pool.target_reserve_ratio);
+        Ok(())
+    }
+
+    /// Split `amount` lamports off a pool stake account into a new one (authority only)
+    ///
+    /// The new account takes the validator's next stake index. Deactivating it
+    /// afterwards unstakes only part of the original delegation.
+    pub fn split_stake_account(
+        ctx: Context<SplitStakeAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
         
-        if pool.liquid_reserve < target_reserve {
-            // Need to unstake from validators
-            let amount_to_unstake = target_reserve.checked_sub(pool.liquid_reserve).unwrap();
-            msg!("Need to unstake {} SOL from validators", 
-                 amount_to_unstake as f64 / 1_000_000_000.0);
-            
-            // In full implementation, this would initiate unstaking
-            // For now, we'll simulate immediate unstaking (devnet testing)
-            if amount_to_unstake <= pool.staked_sol_balance {
-                pool.staked_sol_balance = pool.staked_sol_balance.checked_sub(amount_to_unstake).unwrap();
-                pool.liquid_reserve = pool.liquid_reserve.checked_add(amount_to_unstake).unwrap();
-            }
-        } else if pool.liquid_reserve > target_reserve {
-            // Need to stake more to validators  
-            let amount_to_stake = pool.liquid_reserve.checked_sub(target_reserve).unwrap();
-            msg!("Should stake {} SOL to validators", 
-                 amount_to_stake as f64 / 1_000_000_000.0);
-            
-            // This would be handled by stake_to_validators function
-        }
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        
+        let epoch = Clock::get()?.epoch;
+        let source = &ctx.accounts.source_stake;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let source_record = &mut ctx.accounts.source_record;
+
+        let source_meta = stake::pool_stake_meta(source, &pool.key())?;
+        stake::untrack_transient(pool, validator_info, source_record)?;
+        stake::refresh_stake_record(source_record, &stake::read_stake_state(source)?, epoch);
+        require!(
+            source.lamports().safe_sub(source_meta.rent_exempt_reserve)? == source_record.last_observed_lamports,
+            ErrorCode::UnharvestedRewards
+        );
+        // Harvested fees stay behind in the source account
+        require!(
+            amount < source_record.last_observed_lamports && amount <= source_record.staked_lamports()?,
+            ErrorCode::InsufficientFunds
+        );
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+
+        // Anchor already allocated the split account and assigned it to the
+        // stake program (rent paid by the authority), so only Split is needed
+        stake::split(source, &ctx.accounts.split_stake, &pool.to_account_info(), pool_signer, amount)?;
+
+        let split_principal = amount.min(source_record.principal);
+        source_record.principal = source_record.principal.safe_sub(split_principal)?;
+        source_record.last_observed_lamports = source_record.last_observed_lamports.safe_sub(amount)?;
+
+        let split_record = &mut ctx.accounts.split_record;
+        split_record.pool = pool.key();
+        split_record.validator = validator_info.key();
+        split_record.stake_account = ctx.accounts.split_stake.key();
+        split_record.seed_index = validator_info.next_stake_index;
+        split_record.principal = split_principal;
+        split_record.activation_epoch = source_record.activation_epoch;
+        split_record.deactivation_epoch = source_record.deactivation_epoch;
+        split_record.last_observed_lamports = amount;
+        split_record.state = source_record.state;
+        split_record.bump = ctx.bumps.split_record;
+        stake::track_transient(pool, validator_info, source_record)?;
+        stake::track_transient(pool, validator_info, split_record)?;
+        pool.stake_account_count = pool.stake_account_count.safe_add(1)?;
+
+        validator_info.next_stake_index = validator_info.next_stake_index.safe_add(1)?;
+        
+        msg!("Split {} SOL from {} into {}", 
+             amount as f64 / 1_000_000_000.0,
+             source.key(),
+             ctx.accounts.split_stake.key());
         
         Ok(())
     }
 
-    /// Withdraw protocol fees (authority only)
-    pub fn withdraw_protocol_fees(
-        ctx: Context<WithdrawProtocolFees>,
+    /// Move `amount` of stake from one validator to another (authority only)
+    ///
+    /// The native stake program can only move active stake between accounts
+    /// delegated to the same vote account, so cross-validator moves go through
+    /// a transient account: the amount is split into the destination
+    /// validator's next stake account and deactivated there. Once it has cooled
+    /// down, `complete_redelegation` delegates it to the destination validator.
+    /// Both validators' `total_delegated` move now; the reserve is never touched.
+    /// Concentration caps apply to the destination as in `stake_to_validator`.
+    pub fn redelegate_stake(
+        ctx: Context<RedelegateStake>,
         amount: u64,
     ) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
         
         require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
-        require!(amount <= pool.protocol_fees_earned, ErrorCode::InsufficientFunds);
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(ctx.accounts.destination_validator.is_active, ErrorCode::ValidatorInactive);
         
-        // Transfer fees to authority
-        let pool_seeds = &[b"pool".as_ref(), &[pool.bump]];
-        let pool_signer = &[&pool_seeds[..]];
+        let epoch = ctx.accounts.clock.epoch;
+        let source = &ctx.accounts.source_stake;
+        let source_validator = &mut ctx.accounts.source_validator;
+        let destination_validator = &mut ctx.accounts.destination_validator;
+        let source_record = &mut ctx.accounts.source_record;
 
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: pool.to_account_info(),
-                to: ctx.accounts.authority.to_account_info(),
-            },
-            pool_signer, // Pool PDA signs the transfer
+        let source_meta = stake::pool_stake_meta(source, &pool.key())?;
+        stake::untrack_transient(pool, source_validator, source_record)?;
+        stake::refresh_stake_record(source_record, &stake::read_stake_state(source)?, epoch);
+        require!(
+            source.lamports().safe_sub(source_meta.rent_exempt_reserve)? == source_record.last_observed_lamports,
+            ErrorCode::UnharvestedRewards
         );
-        anchor_lang::system_program::transfer(cpi_context, amount)?;
+        // Harvested fees stay behind in the source account
+        require!(
+            amount < source_record.last_observed_lamports && amount <= source_record.staked_lamports()?,
+            ErrorCode::InsufficientFunds
+        );
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        let pool_info = pool.to_account_info();
+        let transient = &ctx.accounts.destination_stake;
+
+        stake::split(source, transient, &pool_info, pool_signer, amount)?;
+        stake::deactivate(transient, &ctx.accounts.clock.to_account_info(), &pool_info, pool_signer)?;
+
+        let moved_principal = amount.min(source_record.principal);
+        source_record.principal = source_record.principal.safe_sub(moved_principal)?;
+        source_record.last_observed_lamports = source_record.last_observed_lamports.safe_sub(amount)?;
+
+        source_validator.total_delegated = source_validator.total_delegated.safe_sub(amount)?;
+        source_validator.last_update_epoch = epoch;
+        destination_validator.total_delegated = destination_validator.total_delegated.safe_add(amount)?;
+        destination_validator.last_update_epoch = epoch;
+        check_concentration(
+            pool,
+            destination_validator,
+            &[
+                (source_validator.key(), source_validator.total_delegated),
+                (destination_validator.key(), destination_validator.total_delegated),
+            ],
+            ctx.remaining_accounts,
+        )?;
+
+        let destination_record = &mut ctx.accounts.destination_record;
+        destination_record.pool = pool.key();
+        destination_record.validator = destination_validator.key();
+        destination_record.stake_account = transient.key();
+        destination_record.seed_index = destination_validator.next_stake_index;
+        destination_record.principal = moved_principal;
+        destination_record.activation_epoch = source_record.activation_epoch;
+        destination_record.deactivation_epoch = epoch;
+        destination_record.last_observed_lamports = amount;
+        destination_record.state = StakeAccountState::Deactivating;
+        destination_record.bump = ctx.bumps.destination_record;
+        stake::track_transient(pool, source_validator, source_record)?;
+        stake::track_transient(pool, destination_validator, destination_record)?;
+        pool.stake_account_count = pool.stake_account_count.safe_add(1)?;
+
+        destination_validator.next_stake_index = destination_validator.next_stake_index.safe_add(1)?;
         
-        pool.protocol_fees_earned = pool.protocol_fees_earned.checked_sub(amount).unwrap();
+        msg!("Redelegating {} SOL from {} to {} via {}", 
+             amount as f64 / 1_000_000_000.0,
+             source_validator.vote_account,
+             destination_validator.vote_account,
+             transient.key());
         
-        msg!("Withdrew {} SOL protocol fees", amount as f64 / 1_000_000_000.0);
+        Ok(())
+    }
+
+    /// Delegate an inactive pool stake account to the validator it is registered under
+    ///
+    /// Finishes `redelegate_stake` once the transient account has cooled down.
+    pub fn complete_redelegation(
+        ctx: Context<CompleteRedelegation>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(ctx.accounts.validator_info.is_active, ErrorCode::ValidatorInactive);
+        
+        let epoch = ctx.accounts.clock.epoch;
+        let stake_account = &ctx.accounts.stake_account;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        stake::pool_stake_meta(stake_account, &pool.key())?;
+        stake::untrack_transient(pool, validator_info, stake_record)?;
+        stake::refresh_stake_record(stake_record, &stake::read_stake_state(stake_account)?, epoch);
+        require!(stake_record.state == StakeAccountState::Inactive, ErrorCode::StakeNotInactive);
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+
+        stake::delegate(
+            stake_account,
+            &ctx.accounts.validator_vote_account,
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.stake_history,
+            &ctx.accounts.stake_config,
+            &pool.to_account_info(),
+            pool_signer,
+        )?;
+
+        stake_record.activation_epoch = epoch;
+        stake_record.deactivation_epoch = u64::MAX;
+        stake_record.state = StakeAccountState::Activating;
+        stake::track_transient(pool, validator_info, stake_record)?;
+        
+        msg!("Delegated {} SOL in {} to {}", 
+             stake_record.last_observed_lamports as f64 / 1_000_000_000.0,
+             stake_account.key(),
+             ctx.accounts.validator_vote_account.key());
         
         Ok(())
     }
-}
 
-// ============================================================================
-// ACCOUNT STRUCTURES
-// ============================================================================
+    /// Score every validator from its vote account (permissionless crank)
+    ///
+    /// Remaining accounts are (validator_info, vote_account) pairs for every
+    /// validator in index order, so the median covers the whole pool. Scores
+    /// use credits earned in the last completed epoch; rerunning within an
+    /// epoch overwrites that epoch's history entry instead of adding one.
+    pub fn update_validator_scores<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateValidatorScores<'info>>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        let epoch = ctx.accounts.clock.epoch;
+        let scored_epoch = epoch.saturating_sub(1);
 
-#[derive(Accounts)]
-pub struct InitializePool<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    #[account(
-        init,
-        payer = authority,
-        space = 200,
-        seeds = [b"pool"],
-        bump
-    )]
-    pub pool: Account<'info, StakingPool>,
-    
-    #[account(
-        init,
-        payer = authority,
-        mint::decimals = 9,
-        mint::authority = pool,
-    )]
-    pub fluidSOL_mint: Account<'info, Mint>,
-    
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        require!(
+            ctx.remaining_accounts.len() == pool.validator_count as usize * 2,
+            ErrorCode::InvalidValidatorAccounts
+        );
 
-#[derive(Accounts)]
-pub struct AddValidator<'info> {
-    #[account(mut)]
+        let mut validators = Vec::with_capacity(pool.validator_count as usize);
+        for (index, pair) in ctx.remaining_accounts.chunks(2).enumerate() {
+            let (expected, _) = Pubkey::find_program_address(
+                &[b"validator", pool.key().as_ref(), &[index as u8]],
+                &crate::ID,
+            );
+            require_keys_eq!(pair[0].key(), expected, ErrorCode::InvalidValidatorAccounts);
+
+            let validator_info = Account::<ValidatorInfo>::try_from(&pair[0])?;
+            require_keys_eq!(pair[1].key(), validator_info.vote_account, ErrorCode::InvalidVoteAccount);
+            let summary = vote::read_vote_summary(&pair[1])?;
+            validators.push((validator_info, summary));
+        }
+
+        let mut credits: Vec<u64> = validators
+            .iter()
+            .map(|(_, summary)| summary.credits_in_epoch(scored_epoch))
+            .collect();
+        let median_credits = math::median(&mut credits);
+
+        for (validator_info, summary) in validators.iter_mut() {
+            let epoch_credits = summary.credits_in_epoch(scored_epoch);
+            let score = math::validator_score(epoch_credits, median_credits, summary.commission)?;
+
+            if summary.commission != validator_info.commission {
+                validator_info.commission_changed = true;
+                msg!("⚠️ Validator {} changed commission {}% -> {}%",
+                     validator_info.vote_account, validator_info.commission, summary.commission);
+            }
+            if validator_info.last_scored_epoch != epoch {
+                validator_info.score_history.rotate_right(1);
+            }
+
+            validator_info.commission = summary.commission;
+            validator_info.epoch_credits = epoch_credits;
+            validator_info.last_vote_slot = summary.last_vote_slot;
+            validator_info.performance_score = score;
+            validator_info.score_history[0] = score;
+            validator_info.last_scored_epoch = epoch;
+            validator_info.exit(&crate::ID)?;
+
+            msg!("📊 Validator {}: {} credits (median {}), {}% commission, score {}",
+                 validator_info.vote_account, epoch_credits, median_credits, summary.commission, score);
+        }
+        
+        Ok(())
+    }
+
+    /// Quarantine a validator whose commission rose above the pool limit (permissionless)
+    ///
+    /// Triggers when the vote account's commission is above both the commission
+    /// at admission and `max_validator_commission`. The validator is marked
+    /// inactive and the stake accounts passed as (stake_record, stake_account)
+    /// remaining account pairs are deactivated; any missed here can still be
+    /// deactivated by anyone through `deactivate_stake_account`.
+    pub fn check_validator_commission<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CheckValidatorCommission<'info>>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let epoch = ctx.accounts.clock.epoch;
+
+        require!(validator_info.is_active, ErrorCode::ValidatorInactive);
+        let stake_pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(stake_pairs.remainder().is_empty(), ErrorCode::InvalidStakeAccount);
+
+        let commission = vote::read_vote_summary(&ctx.accounts.validator_vote_account)?.commission;
+        if commission != validator_info.commission {
+            validator_info.commission_changed = true;
+            validator_info.commission = commission;
+        }
+
+        if commission <= validator_info.admission_commission || commission <= pool.max_validator_commission {
+            msg!("Validator {} commission {}% is within limits (admitted at {}%, max {}%)",
+                 validator_info.vote_account, commission, validator_info.admission_commission, pool.max_validator_commission);
+            return Ok(());
+        }
+
+        validator_info.is_active = false;
+        validator_info.quarantine_epoch = epoch;
+
+        // Copied out: the pool is mutated between CPIs below
+        let (pool_mint, pool_bump) = (pool.fluid_sol_mint, [pool.bump]);
+        let pool_seeds = &[b"pool".as_ref(), pool_mint.as_ref(), &pool_bump];
+        let pool_signer = &[&pool_seeds[..]];
+        let pool_info = pool.to_account_info();
+        let clock_info = ctx.accounts.clock.to_account_info();
+
+        let mut deactivated: u64 = 0;
+        for pair in stake_pairs {
+            let mut stake_record = Account::<PoolStakeAccount>::try_from(&pair[0])?;
+            let stake_account = &pair[1];
+            require_keys_eq!(stake_record.validator, validator_info.key(), ErrorCode::InvalidStakeAccount);
+            require_keys_eq!(stake_record.stake_account, stake_account.key(), ErrorCode::InvalidStakeAccount);
+
+            stake::pool_stake_meta(stake_account, &pool.key())?;
+            stake::untrack_transient(pool, validator_info, &stake_record)?;
+            stake::refresh_stake_record(&mut stake_record, &stake::read_stake_state(stake_account)?, epoch);
+            if matches!(stake_record.state, StakeAccountState::Activating | StakeAccountState::Active) {
+                stake::deactivate(stake_account, &clock_info, &pool_info, pool_signer)?;
+                stake_record.deactivation_epoch = epoch;
+                stake_record.state = StakeAccountState::Deactivating;
+                deactivated = deactivated.safe_add(stake_record.last_observed_lamports)?;
+            }
+            stake::track_transient(pool, validator_info, &stake_record)?;
+            stake_record.exit(&crate::ID)?;
+        }
+
+        emit!(ValidatorQuarantined {
+            pool: pool.key(),
+            validator: validator_info.key(),
+            vote_account: validator_info.vote_account,
+            admission_commission: validator_info.admission_commission,
+            commission,
+            max_commission: pool.max_validator_commission,
+            epoch,
+        });
+        
+        msg!("🚨 Quarantined validator {}: commission {}% (admitted at {}%), deactivating {} SOL",
+             validator_info.vote_account,
+             commission,
+             validator_info.admission_commission,
+             deactivated as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Upgrade a pool written by an older layout in place (authority only)
+    ///
+    /// The account is grown to the current size (the authority pays the extra
+    /// rent) and upgraded version by version from v1 on. v2 adds
+    /// `stake_account_count`: remaining accounts are every PoolStakeAccount
+    /// record of the pool. v3 adds the rate's booking slot and staleness limit.
+    /// Unversioned pools go through `migrate_legacy_pool`.
+    pub fn migrate_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigratePool<'info>>,
+    ) -> Result<()> {
+        let pool_info = &ctx.accounts.pool;
+        let mut pool: StakingPool = migrate::grow_and_load(
+            pool_info,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            8 + StakingPool::INIT_SPACE,
+        )?;
+
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(pool.version >= 1, ErrorCode::InvalidMigration);
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"pool", pool.fluid_sol_mint.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(pool_info.key(), expected, ErrorCode::InvalidMigration);
+        require!(pool.version < POOL_VERSION, ErrorCode::AlreadyMigrated);
+
+        let from_version = pool.version;
+        if from_version < 2 {
+            let mut seen: Vec<&Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+            for record_info in ctx.remaining_accounts {
+                require!(!seen.contains(&record_info.key), ErrorCode::DuplicateStakeAccount);
+                seen.push(record_info.key);
+                let record = Account::<PoolStakeAccount>::try_from(record_info)?;
+                require_keys_eq!(record.pool, pool_info.key(), ErrorCode::WrongPool);
+            }
+            pool.stake_account_count = seen.len() as u32;
+        }
+        if from_version < 3 {
+            pool.max_rate_staleness_slots = DEFAULT_MAX_RATE_STALENESS_SLOTS;
+            pool.refresh_exchange_rate(&Clock::get()?)?;
+        }
+        pool.version = POOL_VERSION;
+        migrate::store(pool_info, &pool)?;
+        
+        msg!("Migrated pool {} from layout v{} to v{}", pool_info.key(), from_version, POOL_VERSION);
+        
+        Ok(())
+    }
+
+    /// Rebuild an unversioned pool at its current address (authority only)
+    ///
+    /// v0 pools live at `[b"pool"]` and don't record their mint, so the pool is
+    /// recreated at `[b"pool", fluid_sol_mint]` with the `initialize` defaults
+    /// for every later field. Its balances and validator count carry over, the
+    /// reserve and fees move over, the mint authority is handed to the new
+    /// pool, and the old account is closed (its rent goes back to the
+    /// authority). Remaining accounts are every stake account of the old pool:
+    /// their staker and withdrawer move to the new pool, and together they
+    /// must cover its staked balance. Validators are then rebuilt with
+    /// `migrate_validator` and the stake accounts registered with
+    /// `register_legacy_stake`.
+    pub fn migrate_legacy_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateLegacyPool<'info>>,
+    ) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_pool.to_account_info();
+        require_keys_eq!(*legacy_info.owner, crate::ID, ErrorCode::InvalidMigration);
+        let legacy = migrate::load_legacy_pool(&legacy_info.try_borrow_data()?)?;
+
+        require!(ctx.accounts.authority.key() == legacy.authority, ErrorCode::Unauthorized);
+
+        let pool = &mut ctx.accounts.pool;
+        pool.set_inner(migrate::upgrade_legacy_pool(
+            &legacy,
+            ctx.accounts.fluid_sol_mint.key(),
+            ctx.bumps.pool,
+            &ctx.accounts.clock,
+        )?);
+
+        let legacy_signer: &[&[&[u8]]] = &[&[b"pool", &[ctx.bumps.legacy_pool]]];
+        anchor_spl::token_interface::set_authority(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::SetAuthority {
+                    current_authority: legacy_info.clone(),
+                    account_or_mint: ctx.accounts.fluid_sol_mint.to_account_info(),
+                },
+                legacy_signer,
+            ),
+            anchor_spl::token_interface::spl_token_2022::instruction::AuthorityType::MintTokens,
+            Some(pool.key()),
+        )?;
+
+        let mut seen: Vec<&Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut moved_stake: u64 = 0;
+        for stake_account in ctx.remaining_accounts {
+            require!(!seen.contains(&stake_account.key), ErrorCode::DuplicateStakeAccount);
+            seen.push(stake_account.key);
+
+            let meta = stake::pool_stake_meta(stake_account, &legacy_info.key())?;
+            moved_stake = moved_stake.safe_add(stake_account.lamports().safe_sub(meta.rent_exempt_reserve)?)?;
+            stake::authorize_all(
+                stake_account,
+                &pool.key(),
+                &ctx.accounts.clock.to_account_info(),
+                &legacy_info,
+                legacy_signer,
+            )?;
+        }
+        require!(moved_stake >= legacy.staked_sol_balance, ErrorCode::IncompleteStakeAccountSet);
+
+        let backing = pool.liquid_reserve.safe_add(pool.protocol_fees_earned)?;
+        transfer_lamports(&legacy_info, &pool.to_account_info(), backing)?;
+        transfer_lamports(&legacy_info, &ctx.accounts.authority.to_account_info(), legacy_info.lamports())?;
+        legacy_info.assign(&anchor_lang::system_program::ID);
+        legacy_info.resize(0)?;
+        
+        msg!("Migrated legacy pool to {} (layout v{}), {} SOL moved, {} stake accounts ({} SOL) handed over", 
+             pool.key(),
+             POOL_VERSION,
+             backing as f64 / 1_000_000_000.0,
+             seen.len(),
+             moved_stake as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Give a stake account handed over by `migrate_legacy_pool` its PoolStakeAccount record (authority only)
+    ///
+    /// v0 pools kept no per-account records. The new record books as much of
+    /// the stake account as is still unmatched in `legacy_stake`; anything
+    /// above that (unharvested v0 rewards) is picked up by the next
+    /// `harvest_rewards`. The validator must already be rebuilt.
+    pub fn register_legacy_stake(
+        ctx: Context<RegisterLegacyStake>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let stake_account = &ctx.accounts.stake_account;
+        let validator_info = &mut ctx.accounts.validator_info;
+        let epoch = ctx.accounts.clock.epoch;
+
+        let meta = stake::pool_stake_meta(stake_account, &pool.key())?;
+        let state = stake::read_stake_state(stake_account)?;
+        let solana_stake_interface::state::StakeStateV2::Stake(_, delegation_stake, _) = &state else {
+            return err!(ErrorCode::InvalidStakeAccount);
+        };
+        require_keys_eq!(
+            delegation_stake.delegation.voter_pubkey,
+            validator_info.vote_account,
+            ErrorCode::InvalidStakeAccount
+        );
+
+        let delegated = stake_account.lamports().safe_sub(meta.rent_exempt_reserve)?;
+        let booked = delegated.min(pool.legacy_stake);
+        pool.legacy_stake = pool.legacy_stake.safe_sub(booked)?;
+
+        let stake_record = &mut ctx.accounts.stake_record;
+        stake_record.pool = pool.key();
+        stake_record.validator = validator_info.key();
+        stake_record.stake_account = stake_account.key();
+        stake_record.seed_index = u32::MAX; // v0 stake accounts aren't index-seeded
+        stake_record.principal = booked;
+        stake_record.last_observed_lamports = booked;
+        stake_record.bump = ctx.bumps.stake_record;
+        stake::refresh_stake_record(stake_record, &state, epoch);
+        stake::track_transient(pool, validator_info, stake_record)?;
+        pool.stake_account_count = pool.stake_account_count.safe_add(1)?;
+        validator_info.total_delegated = validator_info.total_delegated.safe_add(booked)?;
+        
+        msg!("Registered legacy stake account {} ({} of {} SOL booked)", 
+             stake_account.key(),
+             booked as f64 / 1_000_000_000.0,
+             delegated as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Rebuild a v0 ValidatorInfo under a migrated pool (authority only)
+    ///
+    /// v0 validators have no pool field and sit at `[b"validator", [b"pool"] PDA, index]`.
+    /// The record is recreated at the same index under the new pool in the
+    /// current layout and the old account is closed (its rent goes back to the
+    /// authority). Its stake accounts are then registered with
+    /// `register_legacy_stake`.
+    pub fn migrate_validator(
+        ctx: Context<MigrateValidator>,
+        index: u8,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(index < pool.validator_count, ErrorCode::InvalidMigration);
+        require!(pool.legacy_validators > 0, ErrorCode::AlreadyMigrated);
+
+        let legacy_info = ctx.accounts.legacy_validator.to_account_info();
+        require_keys_eq!(*legacy_info.owner, crate::ID, ErrorCode::InvalidMigration);
+        let legacy = migrate::load_legacy_validator(&legacy_info.try_borrow_data()?)?;
+        require_keys_eq!(
+            legacy.vote_account,
+            ctx.accounts.vote_account.key(),
+            ErrorCode::InvalidVoteAccount
+        );
+
+        let vote_summary = vote::read_vote_summary(&ctx.accounts.vote_account)?;
+        ctx.accounts.validator_info.set_inner(migrate::upgrade_legacy_validator(
+            &legacy,
+            pool.key(),
+            vote_summary.commission,
+        ));
+        pool.legacy_validators -= 1;
+
+        transfer_lamports(&legacy_info, &ctx.accounts.authority.to_account_info(), legacy_info.lamports())?;
+        legacy_info.assign(&anchor_lang::system_program::ID);
+        legacy_info.resize(0)?;
+        
+        msg!("Migrated validator {} to layout v{} under pool {}", 
+             legacy.vote_account, VALIDATOR_VERSION, pool.key());
+        
+        Ok(())
+    }
+
+    /// Recompute every validator's target allocation (permissionless crank)
+    ///
+    /// Remaining accounts are every ValidatorInfo in index order. Static mode
+    /// uses `allocation_percentage`. Performance-weighted mode keeps pinned
+    /// validators at their `allocation_percentage` and splits what is left
+    /// across the other active validators by `performance_score`. Inactive
+    /// validators always get 0.
+    pub fn update_allocations<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateAllocations<'info>>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+
+        require!(
+            ctx.remaining_accounts.len() == pool.validator_count as usize,
+            ErrorCode::InvalidValidatorAccounts
+        );
+
+        let mut validators = Vec::with_capacity(pool.validator_count as usize);
+        for (index, account) in ctx.remaining_accounts.iter().enumerate() {
+            let (expected, _) = Pubkey::find_program_address(
+                &[b"validator", pool.key().as_ref(), &[index as u8]],
+                &crate::ID,
+            );
+            require_keys_eq!(account.key(), expected, ErrorCode::InvalidValidatorAccounts);
+            validators.push(Account::<ValidatorInfo>::try_from(account)?);
+        }
+
+        let fixed_bps = |v: &ValidatorInfo| if v.is_active { v.allocation_percentage as u64 * 100 } else { 0 };
+        let targets: Vec<u64> = match pool.allocation_mode {
+            AllocationMode::Static => validators.iter().map(|v| fixed_bps(v)).collect(),
+            AllocationMode::PerformanceWeighted => {
+                let pinned_bps = validators
+                    .iter()
+                    .filter(|v| v.allocation_pinned)
+                    .try_fold(0u64, |sum, v| sum.safe_add(fixed_bps(v)))?;
+                let weights: Vec<u64> = validators
+                    .iter()
+                    .filter(|v| !v.allocation_pinned && v.is_active)
+                    .map(|v| v.performance_score as u64)
+                    .collect();
+                let mut weighted = math::weighted_allocations(
+                    &weights,
+                    math::BPS_DENOMINATOR.saturating_sub(pinned_bps),
+                )?
+                .into_iter();
+                validators
+                    .iter()
+                    .map(|v| match (v.allocation_pinned, v.is_active) {
+                        (true, _) => fixed_bps(v),
+                        (false, true) => weighted.next().unwrap_or(0).min(pool.max_validator_share_bps as u64),
+                        (false, false) => 0,
+                    })
+                    .collect()
+            }
+        };
+
+        for (validator_info, target_bps) in validators.iter_mut().zip(targets) {
+            validator_info.target_allocation_bps = u16::try_from(target_bps).map_err(|_| error!(ErrorCode::InvalidAllocation))?;
+            validator_info.exit(&crate::ID)?;
+            msg!("🎯 Validator {}: target {} bps (score {}{})",
+                 validator_info.vote_account,
+                 target_bps,
+                 validator_info.performance_score,
+                 if validator_info.allocation_pinned { ", pinned" } else { "" });
+        }
+
+        pool.allocations_updated_epoch = ctx.accounts.clock.epoch;
+        
+        Ok(())
+    }
+
+    /// Set the concentration caps as shares of the pool's total SOL (authority only)
+    pub fn configure_concentration_caps(
+        ctx: Context<ConfigureValidatorPolicy>,
+        max_validator_share_bps: u16,
+        max_tag_share_bps: u16,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(
+            max_validator_share_bps as u64 <= math::BPS_DENOMINATOR && max_tag_share_bps as u64 <= math::BPS_DENOMINATOR,
+            ErrorCode::InvalidAllocation
+        );
+        
+        pool.max_validator_share_bps = max_validator_share_bps;
+        pool.max_tag_share_bps = max_tag_share_bps;
+        
+        msg!("Concentration caps: {} bps per validator, {} bps per tag", 
+             max_validator_share_bps, max_tag_share_bps);
+        
+        Ok(())
+    }
+
+    /// Tag a validator with its data center / client identity (authority only, 0 = untagged)
+    pub fn set_validator_tag(
+        ctx: Context<ConfigureValidator>,
+        concentration_tag: u32,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let validator_info = &mut ctx.accounts.validator_info;
+        validator_info.concentration_tag = concentration_tag;
+        
+        msg!("Validator {} tagged {}", validator_info.vote_account, concentration_tag);
+        
+        Ok(())
+    }
+
+    /// Switch between static and performance-weighted allocation (authority only)
+    pub fn configure_allocation_mode(
+        ctx: Context<ConfigureValidatorPolicy>,
+        allocation_mode: AllocationMode,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        pool.allocation_mode = allocation_mode;
+        
+        msg!("Allocation mode set to {:?}", allocation_mode);
+        
+        Ok(())
+    }
+
+    /// Override a validator's allocation (authority only)
+    ///
+    /// A pinned validator keeps `allocation_percentage` as its target in
+    /// performance-weighted mode instead of being scored.
+    pub fn set_validator_allocation(
+        ctx: Context<ConfigureValidator>,
+        allocation_percentage: u8,
+        pinned: bool,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(allocation_percentage <= 100, ErrorCode::InvalidAllocation);
+        
+        let validator_info = &mut ctx.accounts.validator_info;
+        validator_info.allocation_percentage = allocation_percentage;
+        validator_info.allocation_pinned = pinned;
+        
+        msg!("Validator {} allocation: {}%{}", 
+             validator_info.vote_account,
+             allocation_percentage,
+             if pinned { " (pinned)" } else { "" });
+        
+        Ok(())
+    }
+
+    /// Set the thresholds a vote account must meet to be added (authority only)
+    pub fn configure_admission_criteria(
+        ctx: Context<ConfigureValidatorPolicy>,
+        max_commission: u8,
+        min_epoch_credits: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(max_commission <= 100, ErrorCode::InvalidAllocation);
+        
+        pool.admission_max_commission = max_commission;
+        pool.admission_min_epoch_credits = min_epoch_credits;
+        
+        msg!("Admission criteria: commission <= {}%, epoch credits >= {}", 
+             max_commission, min_epoch_credits);
+        
+        Ok(())
+    }
+
+    /// Ban a vote account from the pool for good (authority only)
+    ///
+    /// Passing the validator's ValidatorInfo also marks it inactive, so its
+    /// stake can be unstaked by anyone through `deactivate_stake_account`.
+    pub fn blacklist_validator(
+        ctx: Context<BlacklistValidator>,
+        vote_account: Pubkey,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let blacklist = &mut ctx.accounts.blacklist;
+        if blacklist.pool == Pubkey::default() {
+            blacklist.pool = pool.key();
+            blacklist.bump = ctx.bumps.blacklist;
+        }
+        require!(!blacklist.vote_accounts.contains(&vote_account), ErrorCode::ValidatorBlacklisted);
+        require!(blacklist.vote_accounts.len() < MAX_BLACKLIST_ENTRIES, ErrorCode::BlacklistFull);
+        blacklist.vote_accounts.push(vote_account);
+        
+        if let Some(validator_info) = ctx.accounts.validator_info.as_mut() {
+            require_keys_eq!(validator_info.vote_account, vote_account, ErrorCode::InvalidVoteAccount);
+            validator_info.is_active = false;
+        }
+        
+        msg!("🚫 Blacklisted vote account {}", vote_account);
+        
+        Ok(())
+    }
+
+    /// Set the commission above which validators are quarantined (authority only)
+    pub fn configure_max_commission(
+        ctx: Context<ConfigureValidatorPolicy>,
+        max_validator_commission: u8,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(max_validator_commission <= 100, ErrorCode::InvalidAllocation);
+        
+        pool.max_validator_commission = max_validator_commission;
+        
+        msg!("Max validator commission set to {}%", max_validator_commission);
+        
+        Ok(())
+    }
+
+    /// Update rewards from validators and adjust exchange rate
+    pub fn update_rewards(
+        ctx: Context<UpdateRewards>,
+        total_rewards_earned: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(total_rewards_earned > 0, ErrorCode::InvalidAmount);
+        
+        // Add rewards to pool (90% to users via exchange rate, 10% to protocol)
+        let clock = Clock::get()?;
+        let (protocol_fee, fee_shares) = pool.apply_rewards(total_rewards_earned, &clock)?;
+        mint_fee_shares(
+            pool,
+            &ctx.accounts.fluid_sol_mint,
+            &ctx.accounts.fee_token_account,
+            &ctx.accounts.token_program,
+            fee_shares,
+        )?;
+        let user_rewards = total_rewards_earned.safe_sub(protocol_fee)?;
+        
+        msg!("Rewards updated: {} SOL total, {} SOL to users, {} SOL protocol fee", 
+             total_rewards_earned as f64 / 1_000_000_000.0,
+             user_rewards as f64 / 1_000_000_000.0,
+             protocol_fee as f64 / 1_000_000_000.0);
+        msg!("New exchange rate: {}", pool.exchange_rate as f64 / 1_000_000_000.0);
+
+        if let Some(rate_history) = ctx.accounts.rate_history.as_mut() {
+            rate_history.record(clock.epoch, pool);
+        }
+        if let Some(price_oracle) = ctx.accounts.price_oracle.as_mut() {
+            price_oracle.update(pool);
+        }
+        
+        Ok(())
+    }
+
+    /// Create the pool's exchange-rate history (authority only)
+    ///
+    /// Once it exists, pass it to `update_rewards` / `harvest_rewards` to
+    /// snapshot the rate every epoch.
+    pub fn initialize_rate_history(
+        ctx: Context<InitializeRateHistory>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let rate_history = &mut ctx.accounts.rate_history;
+        rate_history.pool = pool.key();
+        rate_history.bump = ctx.bumps.rate_history;
+        rate_history.record(Clock::get()?.epoch, pool);
+        
+        msg!("📈 Rate history initialized at {}", pool.exchange_rate as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Create the pool's fSOL price oracle (authority only)
+    ///
+    /// The account is a cache for integrators that read it directly. It
+    /// always holds a rate together with the slot the pool booked it at: it
+    /// moves with the rate when passed to `update_rewards` /
+    /// `harvest_rewards`, and `refresh_price_oracle` lets anyone catch it up
+    /// after any other instruction that books the rate.
+    pub fn initialize_price_oracle(
+        ctx: Context<InitializePriceOracle>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        let price_oracle = &mut ctx.accounts.price_oracle;
+        price_oracle.pool = pool.key();
+        price_oracle.bump = ctx.bumps.price_oracle;
+        price_oracle.update(pool);
+        
+        msg!("🔮 Price oracle initialized at {} SOL/fSOL", pool.exchange_rate as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Copy the pool's current exchange rate into its price oracle (permissionless)
+    pub fn refresh_price_oracle(
+        ctx: Context<RefreshPriceOracle>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        ctx.accounts.price_oracle.update(pool);
+        
+        msg!("🔮 Price oracle refreshed: {} SOL/fSOL", pool.exchange_rate as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// SOL-per-fSOL price for integrators (read-only, CPI friendly)
+    ///
+    /// Returns the pool's current rate and the slot it was booked at via
+    /// return data, so it is always the rate deposits and withdrawals use.
+    /// `is_stale` is set when that slot is more than the pool's
+    /// `max_rate_staleness_slots` behind the current one, i.e. rewards
+    /// haven't been booked for longer than the configured maximum.
+    pub fn get_exchange_rate(
+        ctx: Context<GetExchangeRate>,
+    ) -> Result<ExchangeRate> {
+        let pool = &ctx.accounts.pool;
+        
+        Ok(ExchangeRate {
+            exchange_rate: pool.exchange_rate,
+            last_update_slot: pool.rate_update_slot,
+            last_update_epoch: pool.rate_update_epoch,
+            is_stale: pool.rate_is_stale(Clock::get()?.slot),
+        })
+    }
+
+    /// Set how old the booked exchange rate may get before it is reported stale (authority only)
+    pub fn configure_price_oracle(
+        ctx: Context<ConfigurePriceOracle>,
+        max_rate_staleness_slots: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(max_rate_staleness_slots > 0, ErrorCode::InvalidAmount);
+        
+        pool.max_rate_staleness_slots = max_rate_staleness_slots;
+        
+        msg!("🔮 Max rate staleness set to {} slots", max_rate_staleness_slots);
+        
+        Ok(())
+    }
+
+    /// Trailing APY in basis points over the last `window_epochs` (read-only)
+    ///
+    /// Compounds the exchange-rate growth between the oldest snapshot inside
+    /// the window and the latest one. Returned via return data; the dashboard
+    /// queries 7 and 30 epochs.
+    pub fn get_apy(
+        ctx: Context<GetApy>,
+        window_epochs: u64,
+    ) -> Result<u64> {
+        let apy_bps = ctx.accounts.rate_history.apy_bps(window_epochs, EpochSchedule::get()?.slots_per_epoch)?;
+        
+        msg!("📈 {}-epoch APY: {}%", window_epochs, apy_bps as f64 / 100.0);
+        
+        Ok(apy_bps)
+    }
+
+    /// Rebalance pool to maintain target reserve ratio
+    ///
+    /// Read-only: reports how much to stake or unstake. ValidatorInfo accounts
+    /// passed as remaining accounts get their stake compared with their target
+    /// allocation, logging how much to move.
+    pub fn rebalance_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RebalancePool<'info>>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+
+        for account in ctx.remaining_accounts {
+            let validator_info = Account::<ValidatorInfo>::try_from(account)?;
+            require_keys_eq!(validator_info.pool, pool.key(), ErrorCode::WrongPool);
+            let target = validator_info.target_delegation(pool.staked_sol_balance)?;
+            if target > validator_info.total_delegated {
+                msg!("Validator {} below target: stake {} SOL more", 
+                     validator_info.vote_account,
+                     target.safe_sub(validator_info.total_delegated)? as f64 / 1_000_000_000.0);
+            } else if target < validator_info.total_delegated {
+                msg!("Validator {} above target: unstake {} SOL", 
+                     validator_info.vote_account,
+                     validator_info.total_delegated.safe_sub(target)? as f64 / 1_000_000_000.0);
+            }
+        }
+        
+        let total_balance = pool.liquid_reserve.safe_add(pool.staked_sol_balance)?;
+        let current_reserve_ratio = if total_balance > 0 {
+            pool.liquid_reserve.safe_mul(100)?.safe_div(total_balance)?
+        } else {
+            0
+        };
+        
+        let target_reserve = math::percent_of(total_balance, pool.target_reserve_ratio as u64)?;
+        // Deactivating stake is already on its way back to the reserve
+        let incoming_reserve = pool.liquid_reserve.safe_add(pool.deactivating_sol)?;
+        
+        msg!("Current reserve ratio: {}%, target: {}%", 
+             current_reserve_ratio, pool.target_reserve_ratio);
+        msg!("Earning stake: {} SOL, activating: {} SOL, deactivating: {} SOL", 
+             pool.earning_stake()? as f64 / 1_000_000_000.0,
+             pool.activating_sol as f64 / 1_000_000_000.0,
+             pool.deactivating_sol as f64 / 1_000_000_000.0);
+        
+        if incoming_reserve < target_reserve {
+            // Need to unstake from validators
+            let amount_to_unstake = target_reserve.safe_sub(incoming_reserve)?;
+            msg!("Need to unstake {} SOL from validators", 
+                 amount_to_unstake as f64 / 1_000_000_000.0);
+            
+            // Handled by split_stake_account + deactivate_stake_account
+        } else if pool.liquid_reserve > target_reserve {
+            // Need to stake more to validators  
+            let amount_to_stake = pool.liquid_reserve.safe_sub(target_reserve)?;
+            msg!("Should stake {} SOL to validators", 
+                 amount_to_stake as f64 / 1_000_000_000.0);
+            
+            // Handled by stake_to_validator
+        }
+        
+        Ok(())
+    }
+
+    /// Withdraw protocol fees (authority only)
+    ///
+    /// Until a fee distribution is configured everything goes to the
+    /// authority. After that the split is mandatory: the recipient wallets are
+    /// passed as remaining accounts in config order and each receives its
+    /// basis-point share (the last takes rounding dust).
+    pub fn withdraw_protocol_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawProtocolFees<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(amount <= pool.protocol_fees_earned, ErrorCode::InsufficientFunds);
+        
+        let pool_info = pool.to_account_info();
+        let fee_distribution = &ctx.accounts.fee_distribution;
+        if fee_distribution.data_is_empty() {
+            // No distribution configured: transfer fees to authority
+            transfer_lamports(&pool_info, &ctx.accounts.authority.to_account_info(), amount)?;
+        } else {
+            // Only this program can create an account at the PDA
+            let distribution = FeeDistribution::try_deserialize(&mut &fee_distribution.try_borrow_data()?[..])?;
+            let recipients = &distribution.recipients;
+            require!(
+                ctx.remaining_accounts.len() == recipients.len(),
+                ErrorCode::InvalidFeeRecipient
+            );
+
+            let mut remaining = amount;
+            for (i, (recipient, wallet)) in recipients.iter().zip(ctx.remaining_accounts).enumerate() {
+                require!(wallet.key() == recipient.wallet, ErrorCode::InvalidFeeRecipient);
+                let share = if i == recipients.len() - 1 {
+                    remaining
+                } else {
+                    math::bps_of(amount, recipient.share_bps as u64)?
+                };
+                transfer_lamports(&pool_info, wallet, share)?;
+                remaining = remaining.safe_sub(share)?;
+
+                msg!("Fee recipient {}: {} SOL", wallet.key(), share as f64 / 1_000_000_000.0);
+            }
+        }
+        
+        pool.protocol_fees_earned = pool.protocol_fees_earned.safe_sub(amount)?;
+        
+        msg!("Withdrew {} SOL protocol fees", amount as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Set fee recipients and their basis-point shares (authority only)
+    pub fn configure_fee_distribution(
+        ctx: Context<ConfigureFeeDistribution>,
+        recipients: Vec<FeeRecipient>,
+    ) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(
+            !recipients.is_empty() && recipients.len() <= MAX_FEE_RECIPIENTS,
+            ErrorCode::InvalidFeeConfig
+        );
+        let total_bps = recipients
+            .iter()
+            .try_fold(0u64, |total, r| total.safe_add(r.share_bps as u64))?;
+        require!(total_bps == math::BPS_DENOMINATOR, ErrorCode::InvalidFeeConfig);
+        
+        let fee_distribution = &mut ctx.accounts.fee_distribution;
+        fee_distribution.pool = pool.key();
+        fee_distribution.bump = ctx.bumps.fee_distribution;
+        fee_distribution.recipients = recipients;
+        
+        for recipient in fee_distribution.recipients.iter() {
+            msg!("Fee recipient {}: {} bps", recipient.wallet, recipient.share_bps);
+        }
+        
+        Ok(())
+    }
+
+    /// Accept or refuse SPL stake-pool instructions for this pool (authority only)
+    pub fn configure_spl_compat(
+        ctx: Context<ConfigureSplCompat>,
+        enabled: bool,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        pool.spl_compat_enabled = enabled;
+        
+        msg!("SPL stake-pool compatibility: {}", if enabled { "enabled" } else { "disabled" });
+        
+        Ok(())
+    }
+
+    /// SPL stake-pool instructions (1-byte tag) fall through to here, see `spl_compat`
+    pub fn spl_stake_pool_fallback<'info>(
+        program_id: &Pubkey,
+        accounts: &'info [AccountInfo<'info>],
+        data: &[u8],
+    ) -> Result<()> {
+        spl_compat::process(program_id, accounts, data)
+    }
+
+    /// Set the deposit fee and the referrer's cut of it (authority only)
+    pub fn configure_deposit_fees(
+        ctx: Context<ConfigureDepositFees>,
+        deposit_fee_bps: u16,
+        referral_fee_percent: u8,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        require!(deposit_fee_bps as u64 <= math::BPS_DENOMINATOR, ErrorCode::InvalidFeeConfig);
+        require!(referral_fee_percent <= 100, ErrorCode::InvalidFeeConfig);
+        // Deposit fees are minted as fSOL, so they need somewhere to go
+        require!(
+            deposit_fee_bps == 0 || pool.fee_token_account != Pubkey::default(),
+            ErrorCode::MissingFeeAccounts
+        );
+        
+        pool.deposit_fee_bps = deposit_fee_bps;
+        pool.referral_fee_percent = referral_fee_percent;
+        
+        msg!("Deposit fee: {} bps, referral share: {}%", deposit_fee_bps, referral_fee_percent);
+        
+        Ok(())
+    }
+
+    /// Reconcile recorded balances against actual lamports (read-only)
+    ///
+    /// Remaining accounts are (stake_record, stake_account) pairs for every
+    /// registered pool stake account, exactly `stake_account_count` of them.
+    /// The pool PDA's lamports above rent plus each stake account's lamports
+    /// above its rent reserve must cover liquid_reserve + staked_sol_balance +
+    /// protocol_fees_earned. Returns the surplus (e.g. unharvested rewards).
+    pub fn verify_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyPool<'info>>,
+    ) -> Result<u64> {
+        let pool = &ctx.accounts.pool;
+        let pool_info = pool.to_account_info();
+        let rent = Rent::get()?;
+
+        let pool_balance = pool_info
+            .lamports()
+            .safe_sub(rent.minimum_balance(pool_info.data_len()))?;
+
+        require!(ctx.remaining_accounts.len().is_multiple_of(2), ErrorCode::IncompleteStakeAccountSet);
+        let mut seen: Vec<&Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len() / 2);
+        let mut stake_balance: u64 = 0;
+        for pair in ctx.remaining_accounts.chunks(2) {
+            let (record_info, stake_account) = (&pair[0], &pair[1]);
+            require!(!seen.contains(&stake_account.key), ErrorCode::DuplicateStakeAccount);
+            seen.push(stake_account.key);
+
+            // Only stake accounts the pool registered count, not donated ones
+            let record = Account::<PoolStakeAccount>::try_from(record_info)?;
+            require_keys_eq!(record.pool, pool.key(), ErrorCode::WrongPool);
+            require_keys_eq!(record.stake_account, stake_account.key(), ErrorCode::InvalidStakeAccount);
+
+            let meta = stake::pool_stake_meta(stake_account, &pool.key())?;
+            let delegated = stake_account.lamports().safe_sub(meta.rent_exempt_reserve)?;
+            stake_balance = stake_balance.safe_add(delegated)?;
+        }
+        require!(
+            seen.len() == pool.stake_account_count as usize,
+            ErrorCode::IncompleteStakeAccountSet
+        );
+
+        // Legacy stake not registered yet has no record to be counted by
+        let actual_total = pool_balance.safe_add(stake_balance)?;
+        let recorded_total = pool
+            .liquid_reserve
+            .safe_add(pool.staked_sol_balance.safe_sub(pool.legacy_stake)?)?
+            .safe_add(pool.protocol_fees_earned)?;
+
+        msg!("🔍 Pool PDA (minus rent): {} | liquid_reserve: {} + protocol_fees_earned: {}",
+             pool_balance, pool.liquid_reserve, pool.protocol_fees_earned);
+        msg!("🔍 Stake accounts ({}): {} | staked_sol_balance: {}",
+             seen.len(), stake_balance, pool.staked_sol_balance);
+        msg!("🔍 Actual: {} | Recorded: {}", actual_total, recorded_total);
+
+        if actual_total < recorded_total {
+            msg!("❌ Pool is short {} lamports", recorded_total - actual_total);
+            return err!(ErrorCode::AccountingMismatch);
+        }
+        let surplus = actual_total - recorded_total;
+        if surplus > 0 {
+            msg!("⏳ Surplus of {} lamports (unharvested rewards?)", surplus);
+        }
+
+        msg!("✅ Pool accounting verified");
+        Ok(surplus)
+    }
+
+    /// Choose whether future reward fees are kept as lamports or minted as fSOL (authority only)
+    pub fn configure_protocol_fees(
+        ctx: Context<ConfigureProtocolFees>,
+        mint_fees_as_fluid_sol: bool,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(ctx.accounts.authority.key() == pool.authority, ErrorCode::Unauthorized);
+        
+        pool.fee_token_account = ctx.accounts.fee_token_account.key();
+        pool.mint_fees_as_fluid_sol = mint_fees_as_fluid_sol;
+        
+        msg!("Protocol fee account: {}", pool.fee_token_account);
+        msg!("Mint fees as fSOL: {}", mint_fees_as_fluid_sol);
+        
+        Ok(())
+    }
+
+    /// Create the pool's liquid unstake pool, its LP mint and fSOL vault (authority only)
+    ///
+    /// LPs provide SOL for instant fSOL -> SOL swaps and earn the swap fees.
+    /// The fee runs from `max_fee_bps` when a swap drains the liquidity down
+    /// to `min_fee_bps` while at least `target_liquidity` SOL is left.
+    pub fn initialize_unstake_pool(
+        ctx: Context<InitializeUnstakePool>,
+        min_fee_bps: u16,
+        max_fee_bps: u16,
+        target_liquidity: u64,
+    ) -> Result<()> {
+        require!(ctx.accounts.authority.key() == ctx.accounts.pool.authority, ErrorCode::Unauthorized);
+        
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        unstake_pool.pool = ctx.accounts.pool.key();
+        unstake_pool.lp_mint = ctx.accounts.lp_mint.key();
+        unstake_pool.fluid_sol_vault = ctx.accounts.fluid_sol_vault.key();
+        unstake_pool.sol_liquidity = 0;
+        unstake_pool.pending_unstake = 0;
+        unstake_pool.vault_fluid_sol = 0;
+        unstake_pool.locked_lp = 0;
+        unstake_pool.next_ticket_index = 0;
+        unstake_pool.total_fees_earned = 0;
+        unstake_pool.bump = ctx.bumps.unstake_pool;
+        unstake_pool.set_fees(min_fee_bps, max_fee_bps, target_liquidity)?;
+        
+        msg!("Unstake pool initialized: fee {}-{} bps, target liquidity {} SOL",
+             min_fee_bps,
+             max_fee_bps,
+             target_liquidity as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Change the unstake pool's fee curve (authority only)
+    pub fn configure_unstake_fees(
+        ctx: Context<ConfigureUnstakePool>,
+        min_fee_bps: u16,
+        max_fee_bps: u16,
+        target_liquidity: u64,
+    ) -> Result<()> {
+        require!(ctx.accounts.authority.key() == ctx.accounts.pool.authority, ErrorCode::Unauthorized);
+        
+        ctx.accounts.unstake_pool.set_fees(min_fee_bps, max_fee_bps, target_liquidity)?;
+        
+        msg!("Unstake fee: {}-{} bps, target liquidity {} SOL",
+             min_fee_bps,
+             max_fee_bps,
+             target_liquidity as f64 / 1_000_000_000.0);
+        
+        Ok(())
+    }
+
+    /// Provide SOL to the unstake pool for LP shares
+    ///
+    /// Returns the LP tokens minted, priced off everything the pool holds:
+    /// liquidity, fSOL it swapped into the vault and stake being redeemed.
+    /// The first deposit leaves `LOCKED_LP_SHARES` behind for good so the
+    /// share price can't be inflated against later LPs.
+    pub fn add_unstake_liquidity(
+        ctx: Context<UnstakeLiquidity>,
+        lamports: u64,
+    ) -> Result<u64> {
+        require!(lamports > 0, ErrorCode::InvalidAmount);
+        
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        let total_value = unstake_pool.total_value(&ctx.accounts.pool)?;
+        let lp_supply = unstake_pool.lp_supply(ctx.accounts.lp_mint.supply)?;
+        // Value nobody holds shares in would go to whoever deposits first
+        require!(lp_supply > 0 || total_value == 0, ErrorCode::UnownedUnstakeValue);
+        // Same share math as fSOL: 1:1 while there are no LP tokens
+        let mut lp_amount = math::fluid_sol_for_deposit(lamports, total_value, lp_supply)?;
+        if lp_supply == 0 {
+            require!(lp_amount > LOCKED_LP_SHARES, ErrorCode::InvalidAmount);
+            lp_amount = lp_amount.safe_sub(LOCKED_LP_SHARES)?;
+            unstake_pool.locked_lp = LOCKED_LP_SHARES;
+        }
+        require!(lp_amount > 0, ErrorCode::InvalidAmount);
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.provider.to_account_info(),
+                    to: unstake_pool.to_account_info(),
+                },
+            ),
+            lamports,
+        )?;
+
+        let unstake_seeds = unstake_pool.signer_seeds();
+        let unstake_signer = &[&unstake_seeds[..]];
+        anchor_spl::token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.provider_lp_account.to_account_info(),
+                    authority: unstake_pool.to_account_info(),
+                },
+                unstake_signer,
+            ),
+            lp_amount,
+        )?;
+
+        unstake_pool.sol_liquidity = unstake_pool.sol_liquidity.safe_add(lamports)?;
+        
+        msg!("💧 Added {} SOL of unstake liquidity for {} LP",
+             lamports as f64 / 1_000_000_000.0,
+             lp_amount as f64 / 1_000_000_000.0);
+        
+        Ok(lp_amount)
+    }
+
+    /// Burn LP shares for their SOL value, paid from the unstake pool's liquidity
+    ///
+    /// Returns the SOL sent. Value tied up in fSOL or stake being redeemed
+    /// becomes withdrawable once `redeem_unstake_pool_fsol` and
+    /// `claim_unstake_ticket` bring it back as liquidity.
+    pub fn remove_unstake_liquidity(
+        ctx: Context<UnstakeLiquidity>,
+        lp_amount: u64,
+    ) -> Result<u64> {
+        require!(lp_amount > 0, ErrorCode::InvalidAmount);
+        
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        let total_value = unstake_pool.total_value(&ctx.accounts.pool)?;
+        let lp_supply = unstake_pool.lp_supply(ctx.accounts.lp_mint.supply)?;
+        let sol_amount = math::sol_for_withdrawal(lp_amount, total_value, lp_supply)?;
+        require!(sol_amount <= unstake_pool.sol_liquidity, ErrorCode::InsufficientLiquidity);
+
+        anchor_spl::token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.provider_lp_account.to_account_info(),
+                    authority: ctx.accounts.provider.to_account_info(),
+                },
+            ),
+            lp_amount,
+        )?;
+
+        transfer_lamports(&unstake_pool.to_account_info(), &ctx.accounts.provider.to_account_info(), sol_amount)?;
+        unstake_pool.sol_liquidity = unstake_pool.sol_liquidity.safe_sub(sol_amount)?;
+        
+        msg!("💧 Removed {} SOL of unstake liquidity for {} LP",
+             sol_amount as f64 / 1_000_000_000.0,
+             lp_amount as f64 / 1_000_000_000.0);
+        
+        Ok(sol_amount)
+    }
+
+    /// Swap fSOL for SOL instantly against the unstake pool
+    ///
+    /// The fSOL is valued at the staking pool's rate and the depth-dependent
+    /// fee (`math::liquid_unstake_fee_bps`) stays with the LPs. The staking
+    /// pool's liquid reserve is not touched. Returns the SOL sent.
+    pub fn liquid_unstake(
+        ctx: Context<LiquidUnstake>,
+        fluid_sol_amount: u64,
+        min_sol_out: u64,
+    ) -> Result<u64> {
+        require!(fluid_sol_amount > 0, ErrorCode::InvalidAmount);
+        
+        let pool = &ctx.accounts.pool;
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        let sol_value = math::sol_for_withdrawal(fluid_sol_amount, pool.total_sol_deposited, pool.total_fluid_sol_minted)?;
+        require!(sol_value <= unstake_pool.sol_liquidity, ErrorCode::InsufficientLiquidity);
+
+        let fee_bps = math::liquid_unstake_fee_bps(
+            sol_value,
+            unstake_pool.sol_liquidity,
+            unstake_pool.target_liquidity,
+            unstake_pool.min_fee_bps,
+            unstake_pool.max_fee_bps,
+        )?;
+        let fee = math::bps_of(sol_value, fee_bps)?;
+        let sol_out = sol_value.safe_sub(fee)?;
+        require!(sol_out >= min_sol_out, ErrorCode::SlippageExceeded);
+
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::TransferChecked {
+                    from: ctx.accounts.user_fluid_sol_account.to_account_info(),
+                    mint: ctx.accounts.fluid_sol_mint.to_account_info(),
+                    to: ctx.accounts.fluid_sol_vault.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            fluid_sol_amount,
+            ctx.accounts.fluid_sol_mint.decimals,
+        )?;
+
+        transfer_lamports(&unstake_pool.to_account_info(), &ctx.accounts.user.to_account_info(), sol_out)?;
+        unstake_pool.sol_liquidity = unstake_pool.sol_liquidity.safe_sub(sol_out)?;
+        unstake_pool.vault_fluid_sol = unstake_pool.vault_fluid_sol.safe_add(fluid_sol_amount)?;
+        unstake_pool.total_fees_earned = unstake_pool.total_fees_earned.safe_add(fee)?;
+        
+        msg!("⚡ Liquid unstake: {} fSOL for {} SOL (fee: {} SOL, {} bps)",
+             fluid_sol_amount as f64 / 1_000_000_000.0,
+             sol_out as f64 / 1_000_000_000.0,
+             fee as f64 / 1_000_000_000.0,
+             fee_bps);
+        
+        Ok(sol_out)
+    }
+
+    /// Redeem fSOL collected by the unstake pool through the delayed path (permissionless crank)
+    ///
+    /// Burns `fluid_sol_amount` from the vault and splits the SOL it is worth
+    /// off an active pool stake account into the next ticket stake account
+    /// (`[b"unstake_stake", unstake_pool, index]`, rent paid by the caller),
+    /// deactivated and handed to the unstake pool. `claim_unstake_ticket`
+    /// collects it once it has cooled down.
+    pub fn redeem_unstake_pool_fsol(
+        ctx: Context<RedeemUnstakePoolFsol>,
+        fluid_sol_amount: u64,
+    ) -> Result<()> {
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        let ticket_stake = &ctx.accounts.ticket_stake;
+        let clock = ctx.accounts.clock.to_account_info();
+        // Only fSOL bought by swaps, not tokens sent to the vault directly
+        require!(fluid_sol_amount <= unstake_pool.vault_fluid_sol, ErrorCode::InvalidAmount);
+
+        let unstake_seeds = unstake_pool.signer_seeds();
+        let unstake_signer = &[&unstake_seeds[..]];
+        let sol_amount = withdraw_as_stake(
+            &mut ctx.accounts.pool,
+            &mut ctx.accounts.validator_info,
+            &mut ctx.accounts.stake_record,
+            &ctx.accounts.pool_stake,
+            ticket_stake,
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::Burn {
+                    mint: ctx.accounts.fluid_sol_mint.to_account_info(),
+                    from: ctx.accounts.fluid_sol_vault.to_account_info(),
+                    authority: unstake_pool.to_account_info(),
+                },
+                unstake_signer,
+            ),
+            fluid_sol_amount,
+        )?;
+
+        let pool = &ctx.accounts.pool;
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        let pool_info = pool.to_account_info();
+        stake::deactivate(ticket_stake, &clock, &pool_info, pool_signer)?;
+        stake::authorize_all(ticket_stake, &unstake_pool.key(), &clock, &pool_info, pool_signer)?;
+
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.unstake_pool = unstake_pool.key();
+        ticket.stake_account = ticket_stake.key();
+        ticket.payer = ctx.accounts.payer.key();
+        ticket.lamports = sol_amount;
+        ticket.bump = ctx.bumps.ticket;
+
+        unstake_pool.pending_unstake = unstake_pool.pending_unstake.safe_add(sol_amount)?;
+        unstake_pool.vault_fluid_sol = unstake_pool.vault_fluid_sol.safe_sub(fluid_sol_amount)?;
+        unstake_pool.next_ticket_index = unstake_pool.next_ticket_index.safe_add(1)?;
+        
+        msg!("Redeeming {} fSOL from the unstake pool as {} SOL of deactivating stake {}",
+             fluid_sol_amount as f64 / 1_000_000_000.0,
+             sol_amount as f64 / 1_000_000_000.0,
+             ticket_stake.key());
+        
+        Ok(())
+    }
+
+    /// Collect a cooled-down ticket stake account into the unstake pool's liquidity (permissionless)
+    ///
+    /// Rewards earned before the deactivation took effect go to the LPs; the
+    /// rent reserve goes back to whoever paid it and the ticket is closed.
+    pub fn claim_unstake_ticket(
+        ctx: Context<ClaimUnstakeTicket>,
+    ) -> Result<()> {
+        let unstake_pool = &mut ctx.accounts.unstake_pool;
+        let ticket_stake = &ctx.accounts.ticket_stake;
+        let ticket = &ctx.accounts.ticket;
+
+        let state = stake::read_stake_state(ticket_stake)?;
+        require!(
+            stake::stake_account_state(&state, ctx.accounts.clock.epoch) == StakeAccountState::Inactive,
+            ErrorCode::StakeNotInactive
+        );
+        let solana_stake_interface::state::StakeStateV2::Stake(meta, _, _) = state else {
+            return err!(ErrorCode::InvalidStakeAccount);
+        };
+        let withdrawable = ticket_stake.lamports().safe_sub(meta.rent_exempt_reserve)?;
+
+        let unstake_seeds = unstake_pool.signer_seeds();
+        let unstake_signer = &[&unstake_seeds[..]];
+
+        // Principal + rewards to the LPs, rent back to the payer
+        for (recipient, lamports) in [
+            (unstake_pool.to_account_info(), withdrawable),
+            (ctx.accounts.payer.to_account_info(), meta.rent_exempt_reserve),
+        ] {
+            let withdraw_ix = solana_stake_interface::instruction::withdraw(
+                &ticket_stake.key(),
+                &unstake_pool.key(),
+                &recipient.key(),
+                lamports,
+                None,
+            );
+            anchor_lang::solana_program::program::invoke_signed(
+                &withdraw_ix,
+                &[
+                    ticket_stake.to_account_info(),
+                    recipient,
+                    ctx.accounts.clock.to_account_info(),
+                    ctx.accounts.stake_history.to_account_info(),
+                    unstake_pool.to_account_info(),
+                ],
+                unstake_signer,
+            )?;
+        }
+
+        unstake_pool.pending_unstake = unstake_pool.pending_unstake.safe_sub(ticket.lamports)?;
+        unstake_pool.sol_liquidity = unstake_pool.sol_liquidity.safe_add(withdrawable)?;
+        
+        msg!("Claimed {} SOL from unstake ticket {} into unstake liquidity",
+             withdrawable as f64 / 1_000_000_000.0,
+             ticket_stake.key());
+        
+        Ok(())
+    }
+}
+
+/// Mint fSOL fee shares to the pool's fee token account (no-op when nothing to mint)
+fn mint_fee_shares<'info>(
+    pool: &Account<'info, StakingPool>,
+    fluid_sol_mint: &Option<InterfaceAccount<'info, Mint>>,
+    fee_token_account: &Option<InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Option<Interface<'info, TokenInterface>>,
+    fee_shares: u64,
+) -> Result<()> {
+    if !pool.mint_fees_as_fluid_sol {
+        return Ok(());
+    }
+    let (Some(mint), Some(fee_account), Some(token_program)) =
+        (fluid_sol_mint, fee_token_account, token_program)
+    else {
+        return err!(ErrorCode::MissingFeeAccounts);
+    };
+    if fee_shares == 0 {
+        return Ok(());
+    }
+
+    let seeds = pool.signer_seeds();
+    let signer = &[&seeds[..]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        anchor_spl::token_interface::MintTo {
+            mint: mint.to_account_info(),
+            to: fee_account.to_account_info(),
+            authority: pool.to_account_info(),
+        },
+        signer,
+    );
+    anchor_spl::token_interface::mint_to(cpi_ctx, fee_shares)?;
+
+    msg!("Minted {} fSOL protocol fee", fee_shares as f64 / 1_000_000_000.0);
+    Ok(())
+}
+
+/// Bytes Token-2022 adds to a mint for a token-metadata extension
+///
+/// TLV header (type + length) + update authority + mint + three
+/// length-prefixed strings + an empty additional-metadata vec.
+fn token_metadata_len(name: &str, symbol: &str, uri: &str) -> usize {
+    2 + 2 + 32 + 32 + (4 + name.len()) + (4 + symbol.len()) + (4 + uri.len()) + 4
+}
+
+/// Metaplex metadata for fSOL (fungible, no royalties or creators)
+fn fluid_sol_metadata(uri: String) -> DataV2 {
+    DataV2 {
+        name: FLUID_SOL_NAME.to_string(),
+        symbol: FLUID_SOL_SYMBOL.to_string(),
+        uri,
+        seller_fee_basis_points: 0,
+        creators: None,
+        collection: None,
+        uses: None,
+    }
+}
+
+/// Shared instant withdrawal path: burn `owner`'s fSOL, pay out of the liquid
+/// reserve net of the instant withdrawal fee. Returns the SOL sent to `recipient`.
+fn withdraw_from_reserve<'info>(
+    pool: &mut Account<'info, StakingPool>,
+    fluid_sol_mint: &InterfaceAccount<'info, Mint>,
+    source: &InterfaceAccount<'info, TokenAccount>,
+    owner: &AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    fluid_sol_amount: u64,
+    recipient: &AccountInfo<'info>,
+) -> Result<u64> {
+    
+    // Validate withdrawal amount
+    require!(fluid_sol_amount > 0, ErrorCode::InvalidAmount);
+    
+    // Calculate SOL to return based on current backing (rounded down in the pool's favor)
+    let sol_to_return = math::sol_for_withdrawal(
+        fluid_sol_amount,
+        pool.total_sol_deposited,
+        pool.total_fluid_sol_minted,
+    )?;
+    
+    // Check if we have enough in liquid reserve for instant withdrawal
+    require!(sol_to_return <= pool.liquid_reserve, ErrorCode::InsufficientLiquidity);
+    
+    // Calculate 0.3% instant withdrawal fee
+    let withdrawal_fee = math::instant_withdrawal_fee(sol_to_return)?;
+    let net_sol_to_user = sol_to_return.safe_sub(withdrawal_fee)?;
+    
+    msg!("Withdrawing {} fSOL for {} SOL (fee: {} SOL)", 
+        fluid_sol_amount as f64 / 1_000_000_000.0,
+        net_sol_to_user as f64 / 1_000_000_000.0,
+        withdrawal_fee as f64 / 1_000_000_000.0);
+
+    // Burn FluidSOL tokens from user's account
+    let cpi_accounts = anchor_spl::token_interface::Burn {
+        mint: fluid_sol_mint.to_account_info(),
+        from: source.to_account_info(),
+        authority: owner.clone(),
+    };
+    let cpi_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        cpi_accounts,
+    );
+    anchor_spl::token_interface::burn(cpi_ctx, fluid_sol_amount)?;
+
+    // Transfer SOL from pool to recipient (direct lamport manipulation - pool has data)
+    transfer_lamports(&pool.to_account_info(), recipient, net_sol_to_user)?;
+
+    // Update pool accounting
+    pool.total_sol_deposited = pool.total_sol_deposited.safe_sub(sol_to_return)?;
+    pool.total_fluid_sol_minted = pool.total_fluid_sol_minted.safe_sub(fluid_sol_amount)?;
+    pool.liquid_reserve = pool.liquid_reserve.safe_sub(sol_to_return)?;
+    pool.protocol_fees_earned = pool.protocol_fees_earned.safe_add(withdrawal_fee)?;
+    pool.refresh_exchange_rate(&Clock::get()?)?;
+
+    msg!("Withdrawal successful! Remaining pool reserve: {} SOL", 
+        pool.liquid_reserve as f64 / 1_000_000_000.0);
+
+    Ok(net_sol_to_user)
+}
+
+/// Burn fSOL and split the SOL it is worth off an active pool stake account
+/// into `split_stake`, which stays under the pool's authorities
+///
+/// Leaving as stake doesn't touch the liquid reserve, so there is no instant
+/// fee. Returns the SOL split off; the caller hands the new account over.
+fn withdraw_as_stake<'info>(
+    pool: &mut Account<'info, StakingPool>,
+    validator_info: &mut Account<'info, ValidatorInfo>,
+    stake_record: &mut Account<'info, PoolStakeAccount>,
+    pool_stake: &AccountInfo<'info>,
+    split_stake: &AccountInfo<'info>,
+    burn: CpiContext<'_, '_, '_, 'info, anchor_spl::token_interface::Burn<'info>>,
+    fluid_sol_amount: u64,
+) -> Result<u64> {
+    let clock = Clock::get()?;
+    let epoch = clock.epoch;
+    require!(fluid_sol_amount > 0, ErrorCode::InvalidAmount);
+
+    let pool_meta = stake::pool_stake_meta(pool_stake, &pool.key())?;
+    stake::untrack_transient(pool, validator_info, stake_record)?;
+    stake::refresh_stake_record(stake_record, &stake::read_stake_state(pool_stake)?, epoch);
+    require!(stake_record.state == StakeAccountState::Active, ErrorCode::IncompatibleStakeAccounts);
+    require!(
+        pool_stake.lamports().safe_sub(pool_meta.rent_exempt_reserve)? == stake_record.last_observed_lamports,
+        ErrorCode::UnharvestedRewards
+    );
+
+    let sol_to_return = math::sol_for_withdrawal(fluid_sol_amount, pool.total_sol_deposited, pool.total_fluid_sol_minted)?;
+    require!(sol_to_return <= stake_record.staked_lamports()?, ErrorCode::InsufficientLiquidity);
+
+    anchor_spl::token_interface::burn(burn, fluid_sol_amount)?;
+
+    let pool_seeds = pool.signer_seeds();
+    let pool_signer = &[&pool_seeds[..]];
+    stake::split(pool_stake, split_stake, &pool.to_account_info(), pool_signer, sol_to_return)?;
+
+    stake_record.principal = stake_record.principal.saturating_sub(sol_to_return);
+    stake_record.last_observed_lamports = stake_record.last_observed_lamports.safe_sub(sol_to_return)?;
+    stake::track_transient(pool, validator_info, stake_record)?;
+    validator_info.total_delegated = validator_info.total_delegated.safe_sub(sol_to_return)?;
+    pool.total_sol_deposited = pool.total_sol_deposited.safe_sub(sol_to_return)?;
+    pool.total_fluid_sol_minted = pool.total_fluid_sol_minted.safe_sub(fluid_sol_amount)?;
+    pool.staked_sol_balance = pool.staked_sol_balance.safe_sub(sol_to_return)?;
+    pool.refresh_exchange_rate(&clock)?;
+
+    Ok(sol_to_return)
+}
+
+/// Move lamports out of a program-owned account (system transfer can't debit accounts with data)
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let from_balance = from.lamports().safe_sub(amount)?;
+    let to_balance = to.lamports().safe_add(amount)?;
+    **from.try_borrow_mut_lamports()? = from_balance;
+    **to.try_borrow_mut_lamports()? = to_balance;
+    Ok(())
+}
+
+/// Enforce the concentration caps after stake moved to `validator`
+///
+/// Shares are of the pool's total SOL. The tag cap needs every ValidatorInfo
+/// in index order (`validators`, usually the remaining accounts); `updated`
+/// holds totals that are newer in memory than in account data.
+fn check_concentration(
+    pool: &Account<StakingPool>,
+    validator: &ValidatorInfo,
+    updated: &[(Pubkey, u64)],
+    validators: &[AccountInfo],
+) -> Result<()> {
+    let max_validator_stake = math::bps_of(pool.total_sol_deposited, pool.max_validator_share_bps as u64)?;
+    require!(validator.total_delegated <= max_validator_stake, ErrorCode::ConcentrationLimitExceeded);
+
+    if validator.concentration_tag == 0 || pool.max_tag_share_bps as u64 >= math::BPS_DENOMINATOR {
+        return Ok(());
+    }
+    require!(validators.len() == pool.validator_count as usize, ErrorCode::InvalidValidatorAccounts);
+
+    let mut tag_stake: u64 = 0;
+    for (index, account) in validators.iter().enumerate() {
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"validator", pool.key().as_ref(), &[index as u8]],
+            &crate::ID,
+        );
+        require_keys_eq!(account.key(), expected, ErrorCode::InvalidValidatorAccounts);
+        require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidValidatorAccounts);
+
+        let info = ValidatorInfo::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+        if info.concentration_tag != validator.concentration_tag {
+            continue;
+        }
+        let delegated = updated
+            .iter()
+            .find(|(key, _)| *key == account.key())
+            .map(|(_, total)| *total)
+            .unwrap_or(info.total_delegated);
+        tag_stake = tag_stake.safe_add(delegated)?;
+    }
+
+    let max_tag_stake = math::bps_of(pool.total_sol_deposited, pool.max_tag_share_bps as u64)?;
+    require!(tag_stake <= max_tag_stake, ErrorCode::ConcentrationLimitExceeded);
+    Ok(())
+}
+
+// ============================================================================
+// ACCOUNT STRUCTURES
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [b"pool", fluid_sol_mint.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = 9,
+        mint::authority = pool,
+        mint::token_program = token_program,
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePoolWithMetadata<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [b"pool", fluid_sol_mint.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = 9,
+        mint::authority = pool,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = pool,
+        extensions::metadata_pointer::metadata_address = fluid_sol_mint,
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTokenMetadata<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    // Token-2022 pools carry their metadata in the mint itself
+    #[account(
+        address = pool.fluid_sol_mint @ ErrorCode::InvalidMint,
+        owner = anchor_spl::token::ID @ ErrorCode::InvalidMint,
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Metaplex metadata PDA, created by the CPI
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), fluid_sol_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTokenMetadata<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    /// CHECK: Metaplex metadata PDA of the pool's mint, owner checked by the CPI
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), pool.fluid_sol_mint.as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    
+    pub token_metadata_program: Program<'info, Metadata>,
+}
+
+#[derive(Accounts)]
+#[instruction(validator_vote_account: Pubkey)]
+pub struct AddValidator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ValidatorInfo::INIT_SPACE,
+        seeds = [b"validator", pool.key().as_ref(), &[pool.validator_count]],
+        bump
+    )]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    /// CHECK: Validator's vote account, parsed in the handler (owner and layout are checked there)
+    #[account(address = validator_vote_account @ ErrorCode::InvalidVoteAccount)]
+    pub vote_account: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ValidatorBlacklist::INIT_SPACE,
+        seeds = [b"blacklist", pool.key().as_ref()],
+        bump
+    )]
+    pub blacklist: Account<'info, ValidatorBlacklist>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(
+    mut,
+    constraint = fluid_sol_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub user_fluid_sol_account: InterfaceAccount<'info, TokenAccount>,
+
+    // Only required when the pool charges a deposit fee
+    #[account(mut, address = pool.fee_token_account @ ErrorCode::InvalidTokenAccount)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> DepositSol<'info> {
+    /// Shared deposit path: take SOL, mint fSOL net of the deposit fee.
+    /// Returns the fSOL minted to the user and the referral fee minted to
+    /// `referrer` (0 without one).
+    fn process_deposit(&mut self, sol_amount: u64, referrer: Option<AccountInfo<'info>>) -> Result<(u64, u64)> {
+        require!(sol_amount > 0, ErrorCode::InvalidAmount);
+        require!(sol_amount >= 1_000_000, ErrorCode::MinimumDeposit); // 0.001 SOL minimum
+        
+        // Calculate FluidSOL tokens to mint (rounded down in the pool's favor)
+        let fluid_sol_to_mint = math::fluid_sol_for_deposit(
+            sol_amount,
+            self.pool.total_sol_deposited,
+            self.pool.total_fluid_sol_minted,
+        )?;
+        require!(fluid_sol_to_mint > 0, ErrorCode::InvalidAmount);
+
+        // Deposit fee is taken in fSOL, the referrer (if any) gets their cut of it
+        let deposit_fee = math::bps_of(fluid_sol_to_mint, self.pool.deposit_fee_bps as u64)?;
+        let referral_fee = match referrer {
+            Some(_) => math::percent_of(deposit_fee, self.pool.referral_fee_percent as u64)?,
+            None => 0,
+        };
+        let manager_fee = deposit_fee.safe_sub(referral_fee)?;
+        let user_fluid_sol = fluid_sol_to_mint.safe_sub(deposit_fee)?;
+        
+        msg!("Depositing {} SOL for {} fSOL (fee: {} fSOL)", 
+             sol_amount as f64 / 1_000_000_000.0,
+             user_fluid_sol as f64 / 1_000_000_000.0,
+             deposit_fee as f64 / 1_000_000_000.0);
+
+        // Transfer SOL from user to pool
+        let cpi_context = CpiContext::new(
+            self.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: self.user.to_account_info(),
+                to: self.pool.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, sol_amount)?;
+
+        // Mint FluidSOL tokens to user, fee account and referrer
+        self.mint_fluid_sol(self.user_fluid_sol_account.to_account_info(), user_fluid_sol)?;
+        if manager_fee > 0 {
+            let fee_account = self.fee_token_account.as_ref().ok_or(ErrorCode::MissingFeeAccounts)?;
+            self.mint_fluid_sol(fee_account.to_account_info(), manager_fee)?;
+        }
+        if let Some(referrer) = referrer {
+            if referral_fee > 0 {
+                self.mint_fluid_sol(referrer, referral_fee)?;
+            }
+        }
+
+        // Update pool state
+        let pool = &mut self.pool;
+        pool.total_sol_deposited = pool.total_sol_deposited.safe_add(sol_amount)?;
+        pool.total_fluid_sol_minted = pool.total_fluid_sol_minted.safe_add(fluid_sol_to_mint)?;
+        
+        // Add to liquid reserve initially (will be rebalanced later)
+        pool.liquid_reserve = pool.liquid_reserve.safe_add(sol_amount)?;
+        pool.refresh_exchange_rate(&Clock::get()?)?;
+
+        msg!("Deposit successful! Pool balance: {} SOL", 
+             pool.total_sol_deposited as f64 / 1_000_000_000.0);
+
+        Ok((user_fluid_sol, referral_fee))
+    }
+
+    fn mint_fluid_sol(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let seeds = self.pool.signer_seeds();
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = anchor_spl::token_interface::MintTo {
+            mint: self.fluid_sol_mint.to_account_info(),
+            to,
+            authority: self.pool.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        anchor_spl::token_interface::mint_to(cpi_ctx, amount)
+    }
+}
+
+#[derive(Accounts)]
+pub struct DepositSolWithReferral<'info> {
+    pub deposit: DepositSol<'info>,
+
+    #[account(
+        mut,
+        constraint = referrer_fluid_sol_account.mint == deposit.fluid_sol_mint.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = referrer_fluid_sol_account.key() != deposit.user_fluid_sol_account.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub referrer_fluid_sol_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = deposit.user,
+        space = 8 + ReferralRecord::INIT_SPACE,
+        seeds = [b"referral", deposit.pool.key().as_ref(), referrer_fluid_sol_account.key().as_ref()],
+        bump
+    )]
+    pub referral_record: Account<'info, ReferralRecord>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(
+    mut,
+    constraint = fluid_sol_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub user_fluid_sol_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct StakeToValidators<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRewards<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    // Only required when the pool mints protocol fees as fSOL
+    #[account(
+        mut,
+        constraint = fluid_sol_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+    )]
+    pub fluid_sol_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, address = pool.fee_token_account @ ErrorCode::InvalidTokenAccount)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    // When set, the new exchange rate is snapshotted for APY tracking
+    #[account(
+        mut,
+        seeds = [b"rate_history", pool.key().as_ref()],
+        bump = rate_history.bump
+    )]
+    pub rate_history: Option<Account<'info, RateHistory>>,
+
+    // When set, the oracle moves atomically with the exchange rate
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Option<Account<'info, PriceOracle>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRateHistory<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RateHistory::INIT_SPACE,
+        seeds = [b"rate_history", pool.key().as_ref()],
+        bump
+    )]
+    pub rate_history: Account<'info, RateHistory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePriceOracle<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceOracle::INIT_SPACE,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump
+    )]
+    pub price_oracle: Account<'info, PriceOracle>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshPriceOracle<'info> {
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Account<'info, PriceOracle>,
+}
+
+#[derive(Accounts)]
+pub struct GetExchangeRate<'info> {
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigurePriceOracle<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct GetApy<'info> {
+    #[account(
+        seeds = [b"rate_history", rate_history.pool.as_ref()],
+        bump = rate_history.bump
+    )]
+    pub rate_history: Account<'info, RateHistory>,
+}
+
+#[derive(Accounts)]
+pub struct RebalancePool<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawProtocolFees<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    /// CHECK: Fee distribution PDA, always passed. Once `configure_fee_distribution`
+    /// has created it, fees are split across its recipients (remaining accounts).
+    #[account(
+        seeds = [b"fee_distribution", pool.key().as_ref()],
+        bump
+    )]
+    pub fee_distribution: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureFeeDistribution<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + FeeDistribution::INIT_SPACE,
+        seeds = [b"fee_distribution", pool.key().as_ref()],
+        bump
+    )]
+    pub fee_distribution: Account<'info, FeeDistribution>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyPool<'info> {
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureSplCompat<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureDepositFees<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct StakeToValidator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    /// CHECK: The stake account is initialized by the program
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"stake",
+            pool.key().as_ref(),
+            validator_info.vote_account.as_ref(),
+            &validator_info.next_stake_index.to_le_bytes()
+        ],
+        bump,
+        space = STAKE_ACCOUNT_SIZE,
+        owner = solana_stake_interface::program::ID
+    )]
+    pub stake_account: AccountInfo<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PoolStakeAccount::INIT_SPACE,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
+        bump
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+    
+    /// CHECK: This is the validator's vote account
+    #[account(address = validator_info.vote_account @ ErrorCode::InvalidVoteAccount)]
+    pub validator_vote_account: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+    
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+    
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+    
+    /// CHECK: Solana native stake history sysvar
+    pub stake_history: AccountInfo<'info>,
+    
+    /// CHECK: Solana native stake config account
+    pub stake_config: AccountInfo<'info>,
+}
+
+// Harvest rewards from one pool stake account
+#[derive(Accounts)]
+pub struct HarvestRewards<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+    
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+    
+    /// CHECK: The stake account to check for rewards (validated against the pool in the handler)
+    pub stake_account: AccountInfo<'info>,
+
+    // Only required when the pool mints protocol fees as fSOL
+    #[account(
+        mut,
+        constraint = fluid_sol_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+    )]
+    pub fluid_sol_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, address = pool.fee_token_account @ ErrorCode::InvalidTokenAccount)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    // When set, the new exchange rate is snapshotted for APY tracking
+    #[account(
+        mut,
+        seeds = [b"rate_history", pool.key().as_ref()],
+        bump = rate_history.bump
+    )]
+    pub rate_history: Option<Account<'info, RateHistory>>,
+
+    // When set, the oracle moves atomically with the exchange rate
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Option<Account<'info, PriceOracle>>,
+}
+
+#[derive(Accounts)]
+pub struct MergeStakeAccounts<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", destination_stake.key().as_ref()],
+        bump = destination_record.bump,
+        constraint = destination_record.validator == validator_info.key() @ ErrorCode::IncompatibleStakeAccounts
+    )]
+    pub destination_record: Account<'info, PoolStakeAccount>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"stake_record", source_stake.key().as_ref()],
+        bump = source_record.bump,
+        constraint = source_record.validator == validator_info.key() @ ErrorCode::IncompatibleStakeAccounts
+    )]
+    pub source_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub destination_stake: AccountInfo<'info>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut, constraint = source_stake.key() != destination_stake.key() @ ErrorCode::DuplicateStakeAccount)]
+    pub source_stake: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana native stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SplitStakeAccount<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", source_stake.key().as_ref()],
+        bump = source_record.bump,
+        constraint = source_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub source_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub source_stake: AccountInfo<'info>,
+
+    /// CHECK: Created for the stake program, initialized by the Split CPI
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"stake",
+            pool.key().as_ref(),
+            validator_info.vote_account.as_ref(),
+            &validator_info.next_stake_index.to_le_bytes()
+        ],
+        bump,
+        space = STAKE_ACCOUNT_SIZE,
+        owner = solana_stake_interface::program::ID
+    )]
+    pub split_stake: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PoolStakeAccount::INIT_SPACE,
+        seeds = [b"stake_record", split_stake.key().as_ref()],
+        bump
+    )]
+    pub split_record: Account<'info, PoolStakeAccount>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RedelegateStake<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub source_validator: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        has_one = pool @ ErrorCode::WrongPool,
+        constraint = destination_validator.key() != source_validator.key() @ ErrorCode::InvalidValidatorIndex
+    )]
+    pub destination_validator: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", source_stake.key().as_ref()],
+        bump = source_record.bump,
+        constraint = source_record.validator == source_validator.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub source_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub source_stake: AccountInfo<'info>,
+
+    /// CHECK: Destination validator's next stake account, initialized by the Split CPI
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"stake",
+            pool.key().as_ref(),
+            destination_validator.vote_account.as_ref(),
+            &destination_validator.next_stake_index.to_le_bytes()
+        ],
+        bump,
+        space = STAKE_ACCOUNT_SIZE,
+        owner = solana_stake_interface::program::ID
+    )]
+    pub destination_stake: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PoolStakeAccount::INIT_SPACE,
+        seeds = [b"stake_record", destination_stake.key().as_ref()],
+        bump
+    )]
+    pub destination_record: Account<'info, PoolStakeAccount>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteRedelegation<'info> {
     pub authority: Signer<'info>,
     
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub stake_account: AccountInfo<'info>,
+
+    /// CHECK: This is the validator's vote account
+    #[account(address = validator_info.vote_account @ ErrorCode::InvalidVoteAccount)]
+    pub validator_vote_account: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana native stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Solana native stake config account
+    pub stake_config: AccountInfo<'info>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateValidatorScores<'info> {
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CheckValidatorCommission<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    /// CHECK: This is the validator's vote account
+    #[account(address = validator_info.vote_account @ ErrorCode::InvalidVoteAccount)]
+    pub validator_vote_account: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Pool in an older layout, validated in the handler after it is grown
     #[account(mut)]
+    pub pool: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyPool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: v0 pool, owner and layout are checked in the handler
+    #[account(mut, seeds = [b"pool"], bump)]
+    pub legacy_pool: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [b"pool", fluid_sol_mint.key().as_ref()],
+        bump
+    )]
     pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        mint::token_program = token_program,
+        constraint = fluid_sol_mint.mint_authority == COption::Some(legacy_pool.key()) @ ErrorCode::InvalidMint
+    )]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterLegacyStake<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
     
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    /// CHECK: Stake account handed over by migrate_legacy_pool (validated in the handler)
+    pub stake_account: AccountInfo<'info>,
+
     #[account(
         init,
         payer = authority,
-        space = 8 + 32 + 1 + 8 + 8 + 1 + 1, // ValidatorInfo structure
-        seeds = [b"validator", pool.key().as_ref(), &[pool.validator_count]],
+        space = 8 + PoolStakeAccount::INIT_SPACE,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
         bump
     )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u8)]
+pub struct MigrateValidator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    /// CHECK: Address of the v0 pool the validator was added under (already closed)
+    #[account(seeds = [b"pool"], bump)]
+    pub legacy_pool: UncheckedAccount<'info>,
+
+    /// CHECK: v0 ValidatorInfo, parsed in the handler (owner and layout are checked there)
+    #[account(
+        mut,
+        seeds = [b"validator", legacy_pool.key().as_ref(), &[index]],
+        bump
+    )]
+    pub legacy_validator: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ValidatorInfo::INIT_SPACE,
+        seeds = [b"validator", pool.key().as_ref(), &[index]],
+        bump
+    )]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    /// CHECK: The legacy validator's vote account, parsed in the handler (owner and layout are checked there)
+    pub vote_account: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAllocations<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureValidator<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
     pub validator_info: Account<'info, ValidatorInfo>,
+}
+
+#[derive(Accounts)]
+pub struct BlacklistValidator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
     
+    #[account(
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ValidatorBlacklist::INIT_SPACE,
+        seeds = [b"blacklist", pool.key().as_ref()],
+        bump
+    )]
+    pub blacklist: Account<'info, ValidatorBlacklist>,
+
+    // Only when the vote account is already one of the pool's validators
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Option<Account<'info, ValidatorInfo>>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DepositSol<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+pub struct ConfigureValidatorPolicy<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+}
+
+#[derive(Accounts)]
+pub struct DeactivateStakeAccount<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account, the stake program checks the pool is its staker
+    #[account(mut)]
+    pub stake_account: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStakeAccount<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakingPool>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
+    pub validator_info: Account<'info, ValidatorInfo>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"stake_record", stake_account.key().as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub stake_account: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana native stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureProtocolFees<'info> {
+    pub authority: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
-    
+
     #[account(
-    mut,
-    constraint = fluidSOL_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+        constraint = fluid_sol_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
     )]
-    pub fluidSOL_mint: Account<'info, Mint>,
-    
-    #[account(mut)]
-    pub user_fluidSOL_account: Account<'info, TokenAccount>,
-    
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = fee_token_account.mint == fluid_sol_mint.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub fee_token_account: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawSol<'info> {
+pub struct InitializeUnstakePool<'info> {
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
     
     #[account(
-        mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
-    
+
+    #[account(address = pool.fluid_sol_mint @ ErrorCode::InvalidMint)]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+
     #[account(
-    mut,
-    constraint = fluidSOL_mint.mint_authority == COption::Some(pool.key()) @ ErrorCode::InvalidMint
+        init,
+        payer = authority,
+        space = 8 + UnstakePool::INIT_SPACE,
+        seeds = [b"unstake_pool", pool.key().as_ref()],
+        bump
     )]
-    pub fluidSOL_mint: Account<'info, Mint>,
-    
-    #[account(mut)]
-    pub user_fluidSOL_account: Account<'info, TokenAccount>,
-    
+    pub unstake_pool: Account<'info, UnstakePool>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_mint", unstake_pool.key().as_ref()],
+        bump,
+        mint::decimals = 9,
+        mint::authority = unstake_pool,
+        mint::token_program = token_program
+    )]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"unstake_vault", unstake_pool.key().as_ref()],
+        bump,
+        token::mint = fluid_sol_mint,
+        token::authority = unstake_pool,
+        token::token_program = token_program
+    )]
+    pub fluid_sol_vault: InterfaceAccount<'info, TokenAccount>,
+
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct StakeToValidators<'info> {
+pub struct ConfigureUnstakePool<'info> {
     pub authority: Signer<'info>,
     
     #[account(
-        mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
-}
 
-#[derive(Accounts)]
-pub struct UpdateRewards<'info> {
-    pub authority: Signer<'info>,
-    
     #[account(
         mut,
-        seeds = [b"pool"],
-        bump = pool.bump
+        seeds = [b"unstake_pool", pool.key().as_ref()],
+        bump = unstake_pool.bump
     )]
-    pub pool: Account<'info, StakingPool>,
+    pub unstake_pool: Account<'info, UnstakePool>,
 }
 
 #[derive(Accounts)]
-pub struct RebalancePool<'info> {
-    pub authority: Signer<'info>,
+pub struct UnstakeLiquidity<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
     
     #[account(
-        mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"unstake_pool", pool.key().as_ref()],
+        bump = unstake_pool.bump
+    )]
+    pub unstake_pool: Account<'info, UnstakePool>,
+
+    #[account(mut, address = unstake_pool.lp_mint @ ErrorCode::InvalidMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = provider_lp_account.mint == lp_mint.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub provider_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawProtocolFees<'info> {
+pub struct LiquidUnstake<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub user: Signer<'info>,
     
     #[account(
-        mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
 
-    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [b"unstake_pool", pool.key().as_ref()],
+        bump = unstake_pool.bump
+    )]
+    pub unstake_pool: Account<'info, UnstakePool>,
+
+    #[account(address = pool.fluid_sol_mint @ ErrorCode::InvalidMint)]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub user_fluid_sol_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = unstake_pool.fluid_sol_vault @ ErrorCode::InvalidTokenAccount)]
+    pub fluid_sol_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64, slot: u64)]
-pub struct StakeToValidator<'info> {
+pub struct RedeemUnstakePoolFsol<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub payer: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"pool"],
+        seeds = [b"pool", pool.fluid_sol_mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, StakingPool>,
-    
-    #[account(mut)]
+
+    #[account(
+        mut,
+        seeds = [b"unstake_pool", pool.key().as_ref()],
+        bump = unstake_pool.bump
+    )]
+    pub unstake_pool: Account<'info, UnstakePool>,
+
+    #[account(mut, address = pool.fluid_sol_mint @ ErrorCode::InvalidMint)]
+    pub fluid_sol_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = unstake_pool.fluid_sol_vault @ ErrorCode::InvalidTokenAccount)]
+    pub fluid_sol_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, has_one = pool @ ErrorCode::WrongPool)]
     pub validator_info: Account<'info, ValidatorInfo>,
 
-    /// CHECK: The stake account is initialized by the program
+    #[account(
+        mut,
+        seeds = [b"stake_record", pool_stake.key().as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.validator == validator_info.key() @ ErrorCode::InvalidStakeAccount
+    )]
+    pub stake_record: Account<'info, PoolStakeAccount>,
+
+    /// CHECK: Pool stake account (validated against the pool in the handler)
+    #[account(mut)]
+    pub pool_stake: AccountInfo<'info>,
+
+    /// CHECK: Created for the stake program, initialized by the Split CPI
     #[account(
         init,
-        payer = authority,
-        seeds = [b"stake", authority.key().as_ref(), &slot.to_le_bytes()],
+        payer = payer,
+        seeds = [
+            b"unstake_stake",
+            unstake_pool.key().as_ref(),
+            &unstake_pool.next_ticket_index.to_le_bytes()
+        ],
         bump,
         space = STAKE_ACCOUNT_SIZE,
-        owner = anchor_lang::solana_program::stake::program::ID
+        owner = solana_stake_interface::program::ID
     )]
-    pub stake_account: AccountInfo<'info>,
-    
-    /// CHECK: This is the validator's vote account
-    pub validator_vote_account: AccountInfo<'info>,
-    
+    pub ticket_stake: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + UnstakeTicket::INIT_SPACE,
+        seeds = [b"unstake_ticket", ticket_stake.key().as_ref()],
+        bump
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+
+    pub clock: Sysvar<'info, Clock>,
     pub system_program: Program<'info, System>,
-    
+    pub token_program: Interface<'info, TokenInterface>,
+
     /// CHECK: Solana's native stake program
-    #[account(address = anchor_lang::solana_program::stake::program::ID)]
+    #[account(address = solana_stake_interface::program::ID)]
     pub stake_program: AccountInfo<'info>,
-    
-    pub rent: Sysvar<'info, Rent>,
-    pub clock: Sysvar<'info, Clock>,
-    
-    /// CHECK: Solana native stake history sysvar
-    pub stake_history: AccountInfo<'info>,
-    
-    /// CHECK: Solana native stake config account
-    pub stake_config: AccountInfo<'info>,
 }
 
-// 🔥 NEW: Harvest rewards from specific validator
 #[derive(Accounts)]
-pub struct HarvestRewards<'info> {
-    pub authority: Signer<'info>,
-    
+pub struct ClaimUnstakeTicket<'info> {
+    /// CHECK: Gets back the rent it paid for the ticket stake account
+    #[account(mut, address = ticket.payer)]
+    pub payer: AccountInfo<'info>,
+
     #[account(
         mut,
-        seeds = [b"pool"],
-        bump = pool.bump
+        seeds = [b"unstake_pool", unstake_pool.pool.as_ref()],
+        bump = unstake_pool.bump
     )]
-    pub pool: Account<'info, StakingPool>,
-    
+    pub unstake_pool: Account<'info, UnstakePool>,
+
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", ticket_stake.key().as_ref()],
+        bump = ticket.bump,
+        has_one = unstake_pool @ ErrorCode::WrongPool,
+        close = payer
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+
+    /// CHECK: Ticket stake account, bound to the ticket by its seeds
     #[account(mut)]
-    pub validator_info: Account<'info, ValidatorInfo>,
-    
-    /// CHECK: The stake account to check for rewards
-    pub stake_account: AccountInfo<'info>,
+    pub ticket_stake: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Solana native stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Solana's native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
 }
 
 // ============================================================================
@@ -724,11 +3882,12 @@ pub struct HarvestRewards<'info> {
 // ============================================================================
 
 #[account]
+#[derive(InitSpace)]
 pub struct StakingPool {
     pub authority: Pubkey,
     pub total_sol_deposited: u64,       // Total SOL in pool
-    pub total_fluidSOL_minted: u64,     // Total FluidSOL tokens minted
-    pub exchange_rate: u64,             // How much SOL per FluidSOL token
+    pub total_fluid_sol_minted: u64,    // Total FluidSOL tokens minted
+    pub exchange_rate: u64,             // SOL per FluidSOL (1e9 scale), display cache - pricing derives from totals
     pub staked_sol_balance: u64,        // SOL currently staked to validators (70%)
     pub liquid_reserve: u64,            // SOL kept for instant withdrawals (30%)
     pub protocol_fees_earned: u64,      // Protocol revenue (10% of validator rewards)
@@ -736,9 +3895,133 @@ pub struct StakingPool {
     pub validator_count: u8,            // Number of validators in strategy
     pub target_reserve_ratio: u8,       // Target % for liquid reserve (30)
     pub protocol_fee_bps: u16,          // Protocol fee in basis points (1000 = 10%)
+    pub fee_token_account: Pubkey,      // fSOL account receiving minted protocol fees
+    pub mint_fees_as_fluid_sol: bool,   // Mint reward fees as fSOL instead of holding lamports
+    pub deposit_fee_bps: u16,           // Deposit fee taken in fSOL (basis points)
+    pub referral_fee_percent: u8,       // % of the deposit fee paid to referrers
+    pub activating_sol: u64,            // Part of staked_sol_balance still warming up
+    pub deactivating_sol: u64,          // Part of staked_sol_balance cooling down, not yet withdrawable
+    pub max_validator_commission: u8,   // Commission % above which validators are quarantined
+    pub admission_max_commission: u8,   // Highest commission % add_validator accepts
+    pub admission_min_epoch_credits: u64, // Vote credits required in the last epoch to be added
+    pub allocation_mode: AllocationMode, // How validator target allocations are set
+    pub allocations_updated_epoch: u64, // Epoch of the last update_allocations
+    pub max_validator_share_bps: u16,   // Max share of total SOL one validator may hold
+    pub max_tag_share_bps: u16,         // Max share of total SOL validators sharing a tag may hold
+    pub fluid_sol_mint: Pubkey,         // Pool token mint, also the pool PDA seed
+    pub version: u8,                    // Layout version (0 = written before versioning)
+    pub spl_compat_enabled: bool,       // Accept SPL stake-pool instructions (carved from reserved)
+    pub stake_account_count: u32,       // Live PoolStakeAccount records (carved from reserved)
+    pub legacy_validators: u8,          // v0 validators not yet rebuilt by migrate_validator (carved from reserved)
+    pub legacy_stake: u64,              // v0 staked SOL not yet matched by register_legacy_stake (carved from reserved)
+    pub rate_update_slot: u64,          // Slot exchange_rate was last booked (carved from reserved)
+    pub rate_update_epoch: u64,         // Epoch exchange_rate was last booked (carved from reserved)
+    pub max_rate_staleness_slots: u64,  // Age past which the rate is reported stale (carved from reserved)
+    pub reserved: [u8; RESERVED_SPACE - 38], // Room for new fields without a realloc
+}
+
+impl StakingPool {
+    /// Default state for a new pool
+    pub fn initialize(&mut self, authority: Pubkey, fluid_sol_mint: Pubkey, bump: u8, clock: &Clock) {
+        self.authority = authority;
+        self.total_sol_deposited = 0;
+        self.total_fluid_sol_minted = 0;
+        self.exchange_rate = math::RATE_PRECISION; // 1:1 initially
+        self.staked_sol_balance = 0;        // SOL staked to validators
+        self.liquid_reserve = 0;            // SOL kept for instant withdrawals
+        self.protocol_fees_earned = 0;      // Protocol revenue
+        self.bump = bump;
+        self.validator_count = 0;
+        self.target_reserve_ratio = 30;     // 30% reserve target
+        self.protocol_fee_bps = 1000;       // 10% fee in basis points
+        self.fee_token_account = Pubkey::default();
+        self.mint_fees_as_fluid_sol = false; // Fees accrue as lamports until configured
+        self.deposit_fee_bps = 0;
+        self.referral_fee_percent = 0;
+        self.activating_sol = 0;
+        self.deactivating_sol = 0;
+        self.max_validator_commission = DEFAULT_MAX_VALIDATOR_COMMISSION;
+        self.admission_max_commission = DEFAULT_MAX_VALIDATOR_COMMISSION;
+        self.admission_min_epoch_credits = 0;
+        self.allocation_mode = AllocationMode::Static;
+        self.allocations_updated_epoch = 0;
+        self.max_validator_share_bps = math::BPS_DENOMINATOR as u16; // No cap until configured
+        self.max_tag_share_bps = math::BPS_DENOMINATOR as u16;
+        self.fluid_sol_mint = fluid_sol_mint;
+        self.version = POOL_VERSION;
+        self.spl_compat_enabled = false;
+        self.stake_account_count = 0;
+        self.legacy_validators = 0;
+        self.legacy_stake = 0;
+        self.rate_update_slot = clock.slot;
+        self.rate_update_epoch = clock.epoch;
+        self.max_rate_staleness_slots = DEFAULT_MAX_RATE_STALENESS_SLOTS;
+        self.reserved = [0; RESERVED_SPACE - 38];
+    }
+
+    /// Seeds the pool PDA signs with: [b"pool", fluid_sol_mint, bump]
+    pub fn signer_seeds(&self) -> [&[u8]; 3] {
+        [b"pool", self.fluid_sol_mint.as_ref(), std::slice::from_ref(&self.bump)]
+    }
+
+    /// Staked SOL that is fully active and earning rewards
+    pub fn earning_stake(&self) -> Result<u64> {
+        self.staked_sol_balance
+            .safe_sub(self.activating_sol)?
+            .safe_sub(self.deactivating_sol)
+    }
+
+    /// Re-derive the cached exchange rate from current backing and supply
+    ///
+    /// Also stamps the slot and epoch it was booked at, so the pool is the one
+    /// source for both the rate and its age.
+    pub fn refresh_exchange_rate(&mut self, clock: &Clock) -> Result<()> {
+        self.exchange_rate = math::exchange_rate(self.total_sol_deposited, self.total_fluid_sol_minted)?;
+        self.rate_update_slot = clock.slot;
+        self.rate_update_epoch = clock.epoch;
+        Ok(())
+    }
+
+    /// Whether the rate was booked more than `max_rate_staleness_slots` before `slot`
+    pub fn rate_is_stale(&self, slot: u64) -> bool {
+        slot.saturating_sub(self.rate_update_slot) > self.max_rate_staleness_slots
+    }
+
+    /// Book staking rewards, returning (protocol_fee, fee_shares_to_mint)
+    ///
+    /// Lamport mode sets the fee aside in `protocol_fees_earned`. fSOL mode keeps
+    /// the whole reward as backing and mints the fee as fSOL instead, so pool
+    /// lamports always equal backing and the fee keeps compounding.
+    pub fn apply_rewards(&mut self, rewards: u64, clock: &Clock) -> Result<(u64, u64)> {
+        let (user_rewards, protocol_fee) = math::split_rewards(rewards, self.protocol_fee_bps)?;
+
+        let fee_shares = if self.mint_fees_as_fluid_sol {
+            self.staked_sol_balance = self.staked_sol_balance.safe_add(rewards)?;
+            self.total_sol_deposited = self.total_sol_deposited.safe_add(rewards)?;
+            let shares = math::fee_shares(protocol_fee, self.total_sol_deposited, self.total_fluid_sol_minted)?;
+            self.total_fluid_sol_minted = self.total_fluid_sol_minted.safe_add(shares)?;
+            shares
+        } else {
+            self.staked_sol_balance = self.staked_sol_balance.safe_add(user_rewards)?;
+            self.protocol_fees_earned = self.protocol_fees_earned.safe_add(protocol_fee)?;
+            self.total_sol_deposited = self.total_sol_deposited.safe_add(user_rewards)?;
+            0
+        };
+
+        self.refresh_exchange_rate(clock)?;
+        Ok((protocol_fee, fee_shares))
+    }
+
+    /// Book a stake loss against backing (fSOL holders share it via the exchange rate)
+    pub fn apply_slashing(&mut self, loss: u64, clock: &Clock) -> Result<()> {
+        self.staked_sol_balance = self.staked_sol_balance.safe_sub(loss)?;
+        self.total_sol_deposited = self.total_sol_deposited.safe_sub(loss)?;
+        self.refresh_exchange_rate(clock)
+    }
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct ValidatorInfo {
     pub vote_account: Pubkey,           // Validator's vote account
     pub allocation_percentage: u8,      // % of stake to allocate to this validator
@@ -746,6 +4029,271 @@ pub struct ValidatorInfo {
     pub last_update_epoch: u64,         // Last epoch we checked performance
     pub performance_score: u8,          // Performance score (0-100)
     pub is_active: bool,                // Whether validator is active
+    pub next_stake_index: u32,          // Seed index of the next stake account for this validator
+    pub activating_stake: u64,          // Delegated lamports still warming up
+    pub deactivating_stake: u64,        // Delegated lamports cooling down
+    pub transient_settle_epoch: u64,    // Epoch the current transient stake settles (0 if none)
+    pub commission: u8,                 // Commission % seen at the last scoring
+    pub commission_changed: bool,       // Commission differs from an earlier scoring
+    pub epoch_credits: u64,             // Vote credits earned in the last scored epoch
+    pub last_vote_slot: u64,            // Most recent slot the validator voted on
+    pub last_scored_epoch: u64,         // Epoch of the last update_validator_scores (0 if never)
+    pub score_history: [u8; SCORE_HISTORY_LEN], // Performance scores, most recent first
+    pub admission_commission: u8,       // Commission % when the validator was added
+    pub quarantine_epoch: u64,          // Epoch quarantined for a commission increase (0 if never)
+    pub target_allocation_bps: u16,     // Share of staked SOL this validator should hold
+    pub allocation_pinned: bool,        // Keep allocation_percentage in performance-weighted mode
+    pub concentration_tag: u32,         // Data center / client identity group (0 = untagged)
+    pub pool: Pubkey,                   // Pool this validator belongs to
+    pub version: u8,                    // Layout version (0 = written before versioning)
+    pub reserved: [u8; RESERVED_SPACE], // Room for new fields without a realloc
+}
+
+impl ValidatorInfo {
+    /// Lamports this validator should hold out of `total_staked`
+    pub fn target_delegation(&self, total_staked: u64) -> Result<u64> {
+        math::bps_of(total_staked, self.target_allocation_bps as u64)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AllocationMode {
+    Static,                             // Targets follow allocation_percentage
+    PerformanceWeighted,                // Targets follow performance_score, pins excepted
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PoolStakeAccount {
+    pub pool: Pubkey,
+    pub validator: Pubkey,              // ValidatorInfo this stake account belongs to
+    pub stake_account: Pubkey,
+    pub seed_index: u32,                // Index in [b"stake", pool, vote_account, index]
+    pub principal: u64,                 // Lamports staked by the pool (excluding rent)
+    pub activation_epoch: u64,
+    pub deactivation_epoch: u64,        // u64::MAX until deactivated
+    pub last_observed_lamports: u64,    // Lamports above rent at last harvest
+    pub fee_lamports: u64,              // Part of last_observed_lamports booked as protocol_fees_earned
+    pub state: StakeAccountState,
+    pub bump: u8,
+}
+
+impl PoolStakeAccount {
+    /// Lamports of this account counted in the pool's staked_sol_balance
+    pub fn staked_lamports(&self) -> Result<u64> {
+        self.last_observed_lamports.safe_sub(self.fee_lamports)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StakeAccountState {
+    Activating,                         // Delegated, not yet earning
+    Active,                             // Earning rewards
+    Deactivating,                       // Cooling down, not yet withdrawable
+    Inactive,                           // Withdrawable
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ValidatorBlacklist {
+    pub pool: Pubkey,
+    #[max_len(MAX_BLACKLIST_ENTRIES)]
+    pub vote_accounts: Vec<Pubkey>,     // Banned vote accounts
+    pub bump: u8,
+}
+
+/// Compact fSOL price feed for lending protocols and other integrators
+#[account]
+#[derive(InitSpace)]
+pub struct PriceOracle {
+    pub pool: Pubkey,
+    pub exchange_rate: u64,             // SOL per fSOL, 1e9 precision
+    pub last_update_slot: u64,          // Slot the pool booked exchange_rate at
+    pub last_update_epoch: u64,
+    pub bump: u8,
+}
+
+impl PriceOracle {
+    /// Mirror the pool's current exchange rate and the slot it was booked at
+    pub fn update(&mut self, pool: &StakingPool) {
+        self.exchange_rate = pool.exchange_rate;
+        self.last_update_slot = pool.rate_update_slot;
+        self.last_update_epoch = pool.rate_update_epoch;
+    }
+}
+
+/// Return data of `get_exchange_rate`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ExchangeRate {
+    pub exchange_rate: u64,             // SOL per fSOL, 1e9 precision
+    pub last_update_slot: u64,          // Slot the pool booked exchange_rate at
+    pub last_update_epoch: u64,
+    pub is_stale: bool,                 // Rate is older than the pool's max_rate_staleness_slots
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RateHistory {
+    pub pool: Pubkey,
+    #[max_len(RATE_HISTORY_LEN)]
+    pub entries: Vec<RateSnapshot>,     // Ring buffer of epoch snapshots
+    pub head: u16,                      // Index of the latest snapshot
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy)]
+pub struct RateSnapshot {
+    pub epoch: u64,
+    pub exchange_rate: u64,             // SOL per fSOL, 1e9 precision
+    pub total_sol: u64,                 // total_sol_deposited at the snapshot
+    pub supply: u64,                    // total_fluid_sol_minted at the snapshot
+}
+
+impl RateHistory {
+    /// Snapshot the pool for `epoch`, replacing an earlier snapshot from the same epoch
+    pub fn record(&mut self, epoch: u64, pool: &StakingPool) {
+        let snapshot = RateSnapshot {
+            epoch,
+            exchange_rate: pool.exchange_rate,
+            total_sol: pool.total_sol_deposited,
+            supply: pool.total_fluid_sol_minted,
+        };
+        let head = self.head as usize;
+        if self.entries.get(head).is_some_and(|latest| latest.epoch == epoch) {
+            self.entries[head] = snapshot;
+        } else if self.entries.len() < RATE_HISTORY_LEN {
+            self.entries.push(snapshot);
+            self.head = (self.entries.len() - 1) as u16;
+        } else {
+            self.head = ((head + 1) % RATE_HISTORY_LEN) as u16;
+            self.entries[self.head as usize] = snapshot;
+        }
+    }
+
+    /// APY (bps) from the oldest snapshot within `window_epochs` to the latest
+    pub fn apy_bps(&self, window_epochs: u64, slots_per_epoch: u64) -> Result<u64> {
+        let latest = self.entries.get(self.head as usize).ok_or(ErrorCode::InsufficientRateHistory)?;
+        let start = self
+            .entries
+            .iter()
+            .filter(|s| s.epoch < latest.epoch && s.epoch >= latest.epoch.saturating_sub(window_epochs))
+            .min_by_key(|s| s.epoch)
+            .ok_or(ErrorCode::InsufficientRateHistory)?;
+        math::annualized_yield_bps(
+            start.exchange_rate,
+            latest.exchange_rate,
+            latest.epoch.safe_sub(start.epoch)?,
+            slots_per_epoch,
+        )
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeDistribution {
+    pub pool: Pubkey,
+    #[max_len(MAX_FEE_RECIPIENTS)]
+    pub recipients: Vec<FeeRecipient>,  // Shares sum to 10000 bps
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone)]
+pub struct FeeRecipient {
+    pub wallet: Pubkey,                 // Treasury, insurance fund, developers...
+    pub share_bps: u16,                 // Share of withdrawn protocol fees
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ReferralRecord {
+    pub pool: Pubkey,
+    pub referrer: Pubkey,               // Referrer's fSOL token account
+    pub referral_count: u64,            // Deposits made with this referrer
+    pub total_sol_referred: u64,        // SOL deposited through this referrer
+    pub total_fees_earned: u64,         // fSOL paid to this referrer
+    pub bump: u8,
+}
+
+/// SOL liquidity for instant fSOL -> SOL swaps, owned by LP token holders
+#[account]
+#[derive(InitSpace)]
+pub struct UnstakePool {
+    pub pool: Pubkey,
+    pub lp_mint: Pubkey,                // PDA [b"lp_mint", unstake_pool]
+    pub fluid_sol_vault: Pubkey,         // fSOL bought by swaps, PDA [b"unstake_vault", unstake_pool]
+    pub sol_liquidity: u64,             // Lamports available for swaps and LP exits
+    pub pending_unstake: u64,           // SOL in ticket stake accounts not yet claimed
+    pub vault_fluid_sol: u64,           // fSOL swaps put in the vault and not yet redeemed
+    pub locked_lp: u64,                 // LP shares left behind by the first deposit, never redeemable
+    pub target_liquidity: u64,          // Fee is min_fee_bps while this much is left after a swap
+    pub min_fee_bps: u16,
+    pub max_fee_bps: u16,               // Fee for a swap that drains the liquidity
+    pub next_ticket_index: u64,         // Seed index of the next ticket stake account
+    pub total_fees_earned: u64,         // Swap fees kept for LPs
+    pub bump: u8,
+}
+
+impl UnstakePool {
+    /// Seeds the unstake pool PDA signs with: [b"unstake_pool", pool, bump]
+    pub fn signer_seeds(&self) -> [&[u8]; 3] {
+        [b"unstake_pool", self.pool.as_ref(), std::slice::from_ref(&self.bump)]
+    }
+
+    /// Set the fee curve, `min_fee_bps` <= `max_fee_bps` <= 100%
+    pub fn set_fees(&mut self, min_fee_bps: u16, max_fee_bps: u16, target_liquidity: u64) -> Result<()> {
+        require!(
+            min_fee_bps <= max_fee_bps && max_fee_bps as u64 <= math::BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeConfig
+        );
+        self.min_fee_bps = min_fee_bps;
+        self.max_fee_bps = max_fee_bps;
+        self.target_liquidity = target_liquidity;
+        Ok(())
+    }
+
+    /// SOL backing the LP tokens: liquidity, unclaimed tickets and swapped fSOL at the pool rate
+    ///
+    /// Uses the tracked `vault_fluid_sol`, so fSOL donated to the vault can't
+    /// move the share price.
+    pub fn total_value(&self, pool: &StakingPool) -> Result<u64> {
+        let vault_sol = if self.vault_fluid_sol == 0 {
+            0
+        } else {
+            math::sol_for_withdrawal(self.vault_fluid_sol, pool.total_sol_deposited, pool.total_fluid_sol_minted)?
+        };
+        self.sol_liquidity.safe_add(self.pending_unstake)?.safe_add(vault_sol)
+    }
+
+    /// LP shares outstanding, including the locked ones
+    pub fn lp_supply(&self, lp_mint_supply: u64) -> Result<u64> {
+        lp_mint_supply.safe_add(self.locked_lp)
+    }
+}
+
+/// Stake the unstake pool is redeeming fSOL through, claimable once inactive
+#[account]
+#[derive(InitSpace)]
+pub struct UnstakeTicket {
+    pub unstake_pool: Pubkey,
+    pub stake_account: Pubkey,          // PDA [b"unstake_stake", unstake_pool, index]
+    pub payer: Pubkey,                  // Paid the stake account's rent, gets it back on claim
+    pub lamports: u64,                  // SOL split off for the redeemed fSOL
+    pub bump: u8,
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[event]
+pub struct ValidatorQuarantined {
+    pub pool: Pubkey,
+    pub validator: Pubkey,
+    pub vote_account: Pubkey,
+    pub admission_commission: u8,
+    pub commission: u8,
+    pub max_commission: u8,
+    pub epoch: u64,
 }
 
 // ============================================================================
@@ -788,4 +4336,71 @@ pub enum ErrorCode {
     
     #[msg("Validator is not active")]
     ValidatorInactive,
+
+    #[msg("Math overflow")]
+    MathOverflow,
+
+    #[msg("Division by zero")]
+    DivisionByZero,
+
+    #[msg("Fee token account (and mint / token program) required by the pool's fee configuration")]
+    MissingFeeAccounts,
+
+    #[msg("Invalid fee configuration")]
+    InvalidFeeConfig,
+
+    #[msg("Fee recipient accounts do not match the fee distribution")]
+    InvalidFeeRecipient,
+
+    #[msg("Not a stake account owned by this pool")]
+    InvalidStakeAccount,
+
+    #[msg("Stake account passed more than once")]
+    DuplicateStakeAccount,
+
+    #[msg("Pool lamports do not cover recorded balances")]
+    AccountingMismatch,
+
+    #[msg("Stake accounts passed do not match the pool's registered set")]
+    IncompleteStakeAccountSet,
+
+    #[msg("Vote account does not match the validator")]
+    InvalidVoteAccount,
+
+    #[msg("Stake account is not fully deactivated")]
+    StakeNotInactive,
+
+    #[msg("Stake account has unharvested rewards or slashing, harvest first")]
+    UnharvestedRewards,
+
+    #[msg("Stake accounts cannot be merged")]
+    IncompatibleStakeAccounts,
+    #[msg("Expected (validator_info, vote_account) pairs for every validator in index order")]
+    InvalidValidatorAccounts,
+    #[msg("Vote account is blacklisted")]
+    ValidatorBlacklisted,
+    #[msg("Validator blacklist is full")]
+    BlacklistFull,
+    #[msg("Validator commission is above the admission limit")]
+    CommissionTooHigh,
+    #[msg("Validator earned too few vote credits last epoch")]
+    InsufficientEpochCredits,
+    #[msg("Stake would put the validator above its target allocation")]
+    AboveTargetAllocation,
+    #[msg("Stake would exceed the validator or tag concentration cap")]
+    ConcentrationLimitExceeded,
+    #[msg("Account belongs to a different pool")]
+    WrongPool,
+    #[msg("Account cannot be migrated")]
+    InvalidMigration,
+    #[msg("Account is already on the current layout")]
+    AlreadyMigrated,
+    #[msg("Not enough exchange-rate history for this window")]
+    InsufficientRateHistory,
+    #[msg("SPL stake-pool instructions are disabled for this pool")]
+    SplCompatDisabled,
+    #[msg("Swap returns less SOL than the minimum")]
+    SlippageExceeded,
+    #[msg("Unstake pool holds value but has no LP shares")]
+    UnownedUnstakeValue,
 }
\ No newline at end of file
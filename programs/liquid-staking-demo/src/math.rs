@@ -0,0 +1,477 @@
+// ============================================================================
+// EXCHANGE RATE MATH
+// ============================================================================
+//
+// Pure arithmetic behind deposits, withdrawals and reward accounting. Nothing
+// in here touches accounts, so it can be property-tested and fuzzed off-chain.
+// Every operation is checked and surfaces MathOverflow / DivisionByZero
+// instead of panicking.
+//
+// Prices are derived from the pool's SOL backing and fSOL supply on every
+// call, with u128 intermediates. Both directions round down, so any dust
+// stays in the pool rather than leaking to the user.
+
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+/// Exchange rate scale for display: 1_000_000_000 = 1 SOL per fSOL
+pub const RATE_PRECISION: u64 = 1_000_000_000;
+
+/// Basis point denominator (10000 = 100%)
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Slots in a year at the 400ms target slot time
+pub const SLOTS_PER_YEAR: u64 = 78_840_000;
+
+/// Instant withdrawal fee in basis points (0.3%)
+pub const INSTANT_WITHDRAWAL_FEE_BPS: u64 = 30;
+
+/// Checked arithmetic that fails the instruction with a typed error
+pub trait SafeMath: Sized {
+    fn safe_add(self, rhs: Self) -> Result<Self>;
+    fn safe_sub(self, rhs: Self) -> Result<Self>;
+    fn safe_mul(self, rhs: Self) -> Result<Self>;
+    fn safe_div(self, rhs: Self) -> Result<Self>;
+}
+
+macro_rules! impl_safe_math {
+    ($($t:ty),*) => {$(
+        impl SafeMath for $t {
+            fn safe_add(self, rhs: Self) -> Result<Self> {
+                self.checked_add(rhs).ok_or_else(|| error!(ErrorCode::MathOverflow))
+            }
+
+            fn safe_sub(self, rhs: Self) -> Result<Self> {
+                self.checked_sub(rhs).ok_or_else(|| error!(ErrorCode::MathOverflow))
+            }
+
+            fn safe_mul(self, rhs: Self) -> Result<Self> {
+                self.checked_mul(rhs).ok_or_else(|| error!(ErrorCode::MathOverflow))
+            }
+
+            fn safe_div(self, rhs: Self) -> Result<Self> {
+                require!(rhs != 0, ErrorCode::DivisionByZero);
+                Ok(self / rhs)
+            }
+        }
+    )*};
+}
+
+impl_safe_math!(u8, u16, u32, u64, u128);
+
+/// fSOL minted for a SOL deposit, rounded down (1:1 while the pool is empty)
+pub fn fluid_sol_for_deposit(sol_amount: u64, total_sol: u64, fluid_sol_supply: u64) -> Result<u64> {
+    if fluid_sol_supply == 0 {
+        return Ok(sol_amount);
+    }
+    mul_div_floor(sol_amount, fluid_sol_supply, total_sol)
+}
+
+/// SOL owed for burning fSOL, rounded down
+pub fn sol_for_withdrawal(fluid_sol_amount: u64, total_sol: u64, fluid_sol_supply: u64) -> Result<u64> {
+    mul_div_floor(fluid_sol_amount, total_sol, fluid_sol_supply)
+}
+
+/// Fee kept by the pool on an instant withdrawal
+pub fn instant_withdrawal_fee(sol_amount: u64) -> Result<u64> {
+    bps_of(sol_amount, INSTANT_WITHDRAWAL_FEE_BPS)
+}
+
+/// Liquid unstake fee in basis points for taking `sol_out` of `sol_liquidity`
+///
+/// `max_fee_bps` when the swap drains the pool, falling linearly to
+/// `min_fee_bps` as the liquidity left after it approaches `target_liquidity`.
+pub fn liquid_unstake_fee_bps(
+    sol_out: u64,
+    sol_liquidity: u64,
+    target_liquidity: u64,
+    min_fee_bps: u16,
+    max_fee_bps: u16,
+) -> Result<u64> {
+    let remaining = sol_liquidity.safe_sub(sol_out)?;
+    if remaining >= target_liquidity {
+        return Ok(min_fee_bps as u64);
+    }
+    let fee_range = (max_fee_bps as u64).safe_sub(min_fee_bps as u64)?;
+    (max_fee_bps as u64).safe_sub(mul_div_floor(fee_range, remaining, target_liquidity)?)
+}
+
+/// Split validator rewards into (user_rewards, protocol_fee)
+pub fn split_rewards(rewards: u64, protocol_fee_bps: u16) -> Result<(u64, u64)> {
+    let protocol_fee = bps_of(rewards, protocol_fee_bps as u64)?;
+    let user_rewards = rewards.safe_sub(protocol_fee)?;
+    Ok((user_rewards, protocol_fee))
+}
+
+/// fSOL to mint so `fee` lamports of `total_sol` (which already includes the fee) belong to the fee account
+pub fn fee_shares(fee: u64, total_sol: u64, fluid_sol_supply: u64) -> Result<u64> {
+    if fee == 0 {
+        return Ok(0);
+    }
+    fluid_sol_for_deposit(fee, total_sol.safe_sub(fee)?, fluid_sol_supply)
+}
+
+/// SOL per fSOL scaled by RATE_PRECISION (1:1 while the pool is empty)
+pub fn exchange_rate(total_sol: u64, fluid_sol_supply: u64) -> Result<u64> {
+    if fluid_sol_supply == 0 {
+        return Ok(RATE_PRECISION);
+    }
+    mul_div_floor(total_sol, RATE_PRECISION, fluid_sol_supply)
+}
+
+/// floor(a * b / c) computed in u128
+pub fn mul_div_floor(a: u64, b: u64, c: u64) -> Result<u64> {
+    let result = (a as u128).safe_mul(b as u128)?.safe_div(c as u128)?;
+    u64::try_from(result).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+/// `percent`% of `amount`
+pub fn percent_of(amount: u64, percent: u64) -> Result<u64> {
+    amount.safe_mul(percent)?.safe_div(100)
+}
+
+/// `bps` basis points of `amount`
+pub fn bps_of(amount: u64, bps: u64) -> Result<u64> {
+    amount.safe_mul(bps)?.safe_div(BPS_DENOMINATOR)
+}
+
+/// Lower median (0 for an empty slice)
+pub fn median(values: &mut [u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    values[(values.len() - 1) / 2]
+}
+
+/// Split `available_bps` across validators in proportion to their weights
+///
+/// Rounding dust goes to the heaviest weight. All-zero weights split evenly.
+pub fn weighted_allocations(weights: &[u64], available_bps: u64) -> Result<Vec<u64>> {
+    if weights.is_empty() {
+        return Ok(Vec::new());
+    }
+    let even = vec![1; weights.len()];
+    let weights = if weights.iter().all(|w| *w == 0) { &even[..] } else { weights };
+
+    let total_weight = weights.iter().try_fold(0u64, |sum, w| sum.safe_add(*w))?;
+    let mut allocations = weights
+        .iter()
+        .map(|w| mul_div_floor(available_bps, *w, total_weight))
+        .collect::<Result<Vec<u64>>>()?;
+
+    let allocated = allocations.iter().try_fold(0u64, |sum, a| sum.safe_add(*a))?;
+    let heaviest = (0..weights.len()).max_by_key(|i| (weights[*i], std::cmp::Reverse(*i))).unwrap_or(0);
+    allocations[heaviest] = allocations[heaviest].safe_add(available_bps.safe_sub(allocated)?)?;
+    Ok(allocations)
+}
+
+/// Validator score (0-100): vote credits relative to the pool median, capped at
+/// 100, then scaled by the share of rewards the validator leaves to stakers
+pub fn validator_score(credits: u64, median_credits: u64, commission: u8) -> Result<u8> {
+    if median_credits == 0 {
+        return Ok(0);
+    }
+    let credit_score = ((credits as u128).safe_mul(100)?.safe_div(median_credits as u128)?).min(100) as u64;
+    let staker_share = 100u64.saturating_sub(commission as u64);
+    Ok(percent_of(credit_score, staker_share)? as u8)
+}
+
+/// Compounded APY in basis points from the exchange rate moving `start_rate`
+/// -> `end_rate` over `elapsed_epochs`
+///
+/// Whole years' worth of periods compound exactly; the leftover fraction of a
+/// period is applied linearly. A falling rate reports 0.
+pub fn annualized_yield_bps(start_rate: u64, end_rate: u64, elapsed_epochs: u64, slots_per_epoch: u64) -> Result<u64> {
+    let scale = RATE_PRECISION as u128;
+    if end_rate <= start_rate {
+        return Ok(0);
+    }
+    let growth = (end_rate as u128).safe_mul(scale)?.safe_div(start_rate as u128)?;
+    let periods = (SLOTS_PER_YEAR as u128)
+        .safe_mul(scale)?
+        .safe_div((slots_per_epoch as u128).safe_mul(elapsed_epochs as u128)?)?;
+
+    // growth ^ whole periods by repeated squaring
+    let (mut exponent, mut base, mut compounded) = (periods / scale, growth, scale);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            compounded = compounded.safe_mul(base)?.safe_div(scale)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.safe_mul(base)?.safe_div(scale)?;
+        }
+    }
+    let partial = scale.safe_add((growth - scale).safe_mul(periods % scale)?.safe_div(scale)?)?;
+    let compounded = compounded.safe_mul(partial)?.safe_div(scale)?;
+
+    let apy_bps = (compounded - scale).safe_mul(BPS_DENOMINATOR as u128)?.safe_div(scale)?;
+    u64::try_from(apy_bps).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Minimal mirror of the StakingPool fields the math touches
+    #[derive(Debug, Clone, Copy)]
+    struct Pool {
+        total_sol: u64,
+        supply: u64,
+    }
+
+    impl Pool {
+        fn new() -> Self {
+            Pool { total_sol: 0, supply: 0 }
+        }
+
+        fn deposit(&mut self, sol: u64) -> Result<u64> {
+            let minted = fluid_sol_for_deposit(sol, self.total_sol, self.supply)?;
+            self.total_sol = self.total_sol.safe_add(sol)?;
+            self.supply = self.supply.safe_add(minted)?;
+            Ok(minted)
+        }
+
+        fn withdraw(&mut self, fluid_sol: u64) -> Result<u64> {
+            let sol = sol_for_withdrawal(fluid_sol, self.total_sol, self.supply)?;
+            self.total_sol = self.total_sol.safe_sub(sol)?;
+            self.supply = self.supply.safe_sub(fluid_sol)?;
+            let fee = instant_withdrawal_fee(sol)?;
+            sol.safe_sub(fee)
+        }
+
+        fn reward(&mut self, rewards: u64, fee_bps: u16) -> Result<()> {
+            let (user_rewards, _) = split_rewards(rewards, fee_bps)?;
+            self.total_sol = self.total_sol.safe_add(user_rewards)?;
+            Ok(())
+        }
+
+        fn rate(&self) -> u64 {
+            exchange_rate(self.total_sol, self.supply).unwrap()
+        }
+
+        fn is_backed(&self) -> bool {
+            self.supply as u128 * self.rate() as u128 / RATE_PRECISION as u128
+                <= self.total_sol as u128
+        }
+
+        /// SOL per fSOL compared exactly: self >= other
+        fn share_value_ge(&self, other: &Pool) -> bool {
+            self.total_sol as u128 * other.supply as u128
+                >= other.total_sol as u128 * self.supply as u128
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Deposit(u64),
+        Withdraw(u64),
+        Reward(u64, u16),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (1_000_000u64..1_000 * RATE_PRECISION).prop_map(Op::Deposit),
+            (1u64..1_000 * RATE_PRECISION).prop_map(Op::Withdraw),
+            (1u64..10 * RATE_PRECISION, 0u16..=10_000).prop_map(|(r, f)| Op::Reward(r, f)),
+        ]
+    }
+
+    #[test]
+    fn one_to_one_while_empty() {
+        assert_eq!(fluid_sol_for_deposit(2 * RATE_PRECISION, 0, 0).unwrap(), 2 * RATE_PRECISION);
+        assert_eq!(exchange_rate(0, 0).unwrap(), RATE_PRECISION);
+        assert_eq!(split_rewards(1_000, 1000).unwrap(), (900, 100));
+    }
+
+    #[test]
+    fn rounding_favors_the_pool() {
+        // 3 SOL backing 2 fSOL: 1 lamport buys 0.66 fSOL units, 1 unit redeems 1.5 lamports
+        assert_eq!(fluid_sol_for_deposit(1, 3, 2).unwrap(), 0);
+        assert_eq!(sol_for_withdrawal(1, 3, 2).unwrap(), 1);
+        assert_eq!(exchange_rate(3, 2).unwrap(), 1_500_000_000);
+    }
+
+    #[test]
+    fn large_deposits_no_longer_overflow() {
+        // 1B SOL into a pool at 1.5 SOL/fSOL used to overflow the u64 rate scaling
+        let sol = 1_000_000_000 * RATE_PRECISION;
+        let minted = fluid_sol_for_deposit(sol, 3 * sol, 2 * sol).unwrap();
+        assert_eq!(minted, 666_666_666_666_666_666);
+    }
+
+    #[test]
+    fn large_inputs_fail_with_math_overflow() {
+        let overflow: Error = ErrorCode::MathOverflow.into();
+        assert_eq!(fluid_sol_for_deposit(u64::MAX, 1, 2).unwrap_err(), overflow);
+        assert_eq!(sol_for_withdrawal(u64::MAX, 2, 1).unwrap_err(), overflow);
+        assert_eq!(split_rewards(u64::MAX, 1000).unwrap_err(), overflow);
+        assert_eq!(exchange_rate(u64::MAX, 1).unwrap_err(), overflow);
+        assert_eq!(u64::MAX.safe_add(1).unwrap_err(), overflow);
+        assert_eq!(0u64.safe_sub(1).unwrap_err(), overflow);
+    }
+
+    #[test]
+    fn zero_denominators_fail_with_division_by_zero() {
+        let div_zero: Error = ErrorCode::DivisionByZero.into();
+        assert_eq!(fluid_sol_for_deposit(RATE_PRECISION, 0, 1).unwrap_err(), div_zero);
+        assert_eq!(sol_for_withdrawal(RATE_PRECISION, 1, 0).unwrap_err(), div_zero);
+        assert_eq!(1u64.safe_div(0).unwrap_err(), div_zero);
+    }
+
+    #[test]
+    fn liquid_unstake_fee_follows_depth() {
+        let target = 100 * RATE_PRECISION;
+        // Deep pool: the minimum fee
+        assert_eq!(liquid_unstake_fee_bps(RATE_PRECISION, 200 * RATE_PRECISION, target, 30, 300).unwrap(), 30);
+        assert_eq!(liquid_unstake_fee_bps(RATE_PRECISION, 101 * RATE_PRECISION, target, 30, 300).unwrap(), 30);
+        // Half the target left after the swap: halfway between the bounds
+        assert_eq!(liquid_unstake_fee_bps(50 * RATE_PRECISION, target, target, 30, 300).unwrap(), 165);
+        // Drained: the maximum fee
+        assert_eq!(liquid_unstake_fee_bps(target, target, target, 30, 300).unwrap(), 300);
+        assert_eq!(liquid_unstake_fee_bps(0, 0, 0, 30, 300).unwrap(), 30);
+        assert!(liquid_unstake_fee_bps(target + 1, target, target, 30, 300).is_err());
+    }
+
+    #[test]
+    fn weighted_allocations_sum_to_available() {
+        assert_eq!(weighted_allocations(&[], 10_000).unwrap(), Vec::<u64>::new());
+        assert_eq!(weighted_allocations(&[100, 50, 50], 10_000).unwrap(), vec![5_000, 2_500, 2_500]);
+        assert_eq!(weighted_allocations(&[0, 0, 0], 10_000).unwrap(), vec![3_334, 3_333, 3_333]);
+        assert_eq!(weighted_allocations(&[90, 0], 6_000).unwrap(), vec![6_000, 0]);
+        assert_eq!(weighted_allocations(&[33, 33, 34], 10_000).unwrap().iter().sum::<u64>(), 10_000);
+    }
+
+    #[test]
+    fn validator_scores() {
+        assert_eq!(median(&mut []), 0);
+        assert_eq!(median(&mut [30, 10, 20, 40]), 20);
+        assert_eq!(validator_score(1_000, 1_000, 0).unwrap(), 100);
+        assert_eq!(validator_score(2_000, 1_000, 0).unwrap(), 100);
+        assert_eq!(validator_score(500, 1_000, 10).unwrap(), 45);
+        assert_eq!(validator_score(1_000, 1_000, 100).unwrap(), 0);
+        assert_eq!(validator_score(u64::MAX, 1, 0).unwrap(), 100);
+        assert_eq!(validator_score(1_000, 0, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn annualized_yield() {
+        let day_epoch = SLOTS_PER_YEAR / 365;
+        // 0.1% over a one-day epoch compounds to ~44% a year
+        assert_eq!(annualized_yield_bps(RATE_PRECISION, 1_001_000_000, 1, day_epoch).unwrap(), 4_402);
+        // Same growth spread over a week of epochs
+        assert_eq!(annualized_yield_bps(RATE_PRECISION, 1_007_000_000, 7, day_epoch).unwrap(), 4_386);
+        // Window longer than a year: no whole periods, linear only
+        assert_eq!(annualized_yield_bps(RATE_PRECISION, 1_100_000_000, 730, day_epoch).unwrap(), 500);
+        assert_eq!(annualized_yield_bps(1_100_000_000, RATE_PRECISION, 7, day_epoch).unwrap(), 0);
+        assert_eq!(annualized_yield_bps(RATE_PRECISION, RATE_PRECISION, 7, day_epoch).unwrap(), 0);
+        assert!(annualized_yield_bps(RATE_PRECISION, 2 * RATE_PRECISION, 1, 1).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn no_panics_on_any_input(a in any::<u64>(), b in any::<u64>(), c in any::<u64>(), bps in any::<u16>()) {
+            let _ = fluid_sol_for_deposit(a, b, c);
+            let _ = sol_for_withdrawal(a, b, c);
+            let _ = instant_withdrawal_fee(a);
+            let _ = split_rewards(a, bps);
+            let _ = exchange_rate(a, b);
+        }
+
+        #[test]
+        fn round_trip_never_profits(
+            sol in 1u64..u64::MAX / 4,
+            total_sol in 1u64..u64::MAX / 4,
+            supply in 1u64..u64::MAX / 4,
+        ) {
+            let mut pool = Pool { total_sol, supply };
+            if let Ok(minted) = pool.deposit(sol) {
+                if let Ok(back) = sol_for_withdrawal(minted, pool.total_sol, pool.supply) {
+                    prop_assert!(back <= sol);
+                }
+            }
+        }
+
+        #[test]
+        fn fee_shares_never_dilute_holders(
+            total_sol in RATE_PRECISION..u64::MAX / 4,
+            supply in RATE_PRECISION..u64::MAX / 4,
+            rewards in 1u64..1_000 * RATE_PRECISION,
+            bps in 0u16..=10_000,
+        ) {
+            let (user_rewards, fee) = split_rewards(rewards, bps).unwrap();
+            let total_after = total_sol + rewards;
+            let shares = fee_shares(fee, total_after, supply).unwrap();
+            let supply_after = supply + shares;
+
+            // Fee account never gets more than the fee...
+            prop_assert!(sol_for_withdrawal(shares, total_after, supply_after).unwrap() <= fee);
+            // ...and existing holders keep at least their share of the rewards
+            let holders = mul_div_floor(supply, total_after, supply_after).unwrap();
+            prop_assert!(holders >= total_sol + user_rewards);
+        }
+
+        #[test]
+        fn rewards_split_is_exact(rewards in any::<u64>(), bps in 0u16..=10_000) {
+            if let Ok((user, fee)) = split_rewards(rewards, bps) {
+                prop_assert_eq!(user + fee, rewards);
+            }
+        }
+
+        #[test]
+        fn pool_stays_backed(ops in prop::collection::vec(op(), 1..64)) {
+            let mut pool = Pool::new();
+            for op in ops {
+                let before = pool;
+                let applied = match op {
+                    Op::Deposit(sol) => pool.deposit(sol).map(|_| ()),
+                    Op::Withdraw(f) => pool.withdraw(f.min(pool.supply)).map(|_| ()),
+                    Op::Reward(r, bps) => pool.reward(r, bps),
+                };
+                if applied.is_err() {
+                    pool = before;
+                }
+                prop_assert!(pool.is_backed(), "{:?}", pool);
+            }
+        }
+
+        #[test]
+        fn share_value_never_decreases(ops in prop::collection::vec(op(), 1..64)) {
+            let mut pool = Pool::new();
+            let _ = pool.deposit(RATE_PRECISION);
+            for op in ops {
+                let before = pool;
+                let applied = match op {
+                    Op::Deposit(sol) => pool.deposit(sol).map(|_| ()),
+                    // Keep one fSOL outstanding so the share value stays defined
+                    Op::Withdraw(f) => pool.withdraw(f.min(pool.supply.saturating_sub(RATE_PRECISION))).map(|_| ()),
+                    Op::Reward(r, bps) => pool.reward(r, bps),
+                };
+                if applied.is_err() {
+                    pool = before;
+                }
+                prop_assert!(pool.share_value_ge(&before), "{:?} -> {:?}", before, pool);
+            }
+        }
+
+        #[test]
+        fn rate_monotone_under_rewards(
+            deposits in prop::collection::vec(1_000_000u64..1_000 * RATE_PRECISION, 1..16),
+            rewards in prop::collection::vec((1u64..10 * RATE_PRECISION, 0u16..=10_000), 1..16),
+        ) {
+            let mut pool = Pool::new();
+            for sol in deposits {
+                let _ = pool.deposit(sol);
+            }
+            for (r, bps) in rewards {
+                let before = pool.rate();
+                if pool.reward(r, bps).is_ok() {
+                    prop_assert!(pool.rate() >= before);
+                }
+            }
+        }
+    }
+}
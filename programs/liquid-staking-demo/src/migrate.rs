@@ -0,0 +1,312 @@
+// ============================================================================
+// ACCOUNT MIGRATION HELPERS
+// ============================================================================
+//
+// StakingPool and ValidatorInfo carry a `version` byte and reserved padding.
+// Pools written by an older versioned layout are shorter (or have an older
+// version) and can't be loaded as `Account<T>`, so they are grown in place
+// here, deserialized with the new tail zero-filled, upgraded and written back.
+//
+// Pools from before versioning (v0) can't be upgraded in place: they live at
+// `[b"pool"]` rather than `[b"pool", fluid_sol_mint]` and have no mint field,
+// so `migrate_legacy_pool` rebuilds them at the current address instead and
+// hands their stake accounts over. Their validators and stake records are
+// rebuilt afterwards, tracked by `legacy_validators` and `legacy_stake`: a v0
+// ValidatorInfo has no pool field and is addressed by the old pool, so
+// `migrate_validator` recreates it under the new pool and closes the original.
+
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+use crate::{ErrorCode, StakingPool, ValidatorInfo, RESERVED_SPACE, SCORE_HISTORY_LEN, VALIDATOR_VERSION};
+
+/// Size a v0 pool was allocated with
+pub const LEGACY_POOL_LEN: usize = 200;
+
+/// StakingPool as written before versioning, field for field
+#[derive(AnchorDeserialize)]
+pub struct LegacyStakingPool {
+    pub authority: Pubkey,
+    pub total_sol_deposited: u64,
+    pub total_fluid_sol_minted: u64,
+    pub exchange_rate: u64,
+    pub staked_sol_balance: u64,
+    pub liquid_reserve: u64,
+    pub protocol_fees_earned: u64,
+    pub bump: u8,
+    pub validator_count: u8,
+    pub target_reserve_ratio: u8,
+    pub protocol_fee_bps: u16,
+}
+
+/// Parse a v0 pool's account data
+pub fn load_legacy_pool(data: &[u8]) -> Result<LegacyStakingPool> {
+    require!(
+        data.len() == LEGACY_POOL_LEN && data.starts_with(StakingPool::DISCRIMINATOR),
+        ErrorCode::InvalidMigration
+    );
+    LegacyStakingPool::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::InvalidMigration))
+}
+
+/// Size a v0 validator was allocated with
+pub const LEGACY_VALIDATOR_LEN: usize = 8 + 32 + 1 + 8 + 8 + 1 + 1;
+
+/// ValidatorInfo as written before versioning, field for field
+#[derive(AnchorDeserialize)]
+pub struct LegacyValidatorInfo {
+    pub vote_account: Pubkey,
+    pub allocation_percentage: u8,
+    pub total_delegated: u64,
+    pub last_update_epoch: u64,
+    pub performance_score: u8,
+    pub is_active: bool,
+}
+
+/// Parse a v0 validator's account data
+pub fn load_legacy_validator(data: &[u8]) -> Result<LegacyValidatorInfo> {
+    require!(
+        data.len() == LEGACY_VALIDATOR_LEN && data.starts_with(ValidatorInfo::DISCRIMINATOR),
+        ErrorCode::InvalidMigration
+    );
+    LegacyValidatorInfo::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::InvalidMigration))
+}
+
+/// Build the current validator layout from a v0 validator
+///
+/// The v0 fields carry over except `total_delegated`, which is rebuilt by
+/// `register_legacy_stake` as the stake accounts get records. Everything
+/// else gets the `add_validator` defaults, with `commission` read from the
+/// vote account.
+pub fn upgrade_legacy_validator(
+    legacy: &LegacyValidatorInfo,
+    pool: Pubkey,
+    commission: u8,
+) -> ValidatorInfo {
+    ValidatorInfo {
+        vote_account: legacy.vote_account,
+        allocation_percentage: legacy.allocation_percentage,
+        total_delegated: 0,
+        last_update_epoch: legacy.last_update_epoch,
+        performance_score: legacy.performance_score,
+        is_active: legacy.is_active,
+        next_stake_index: 0,
+        activating_stake: 0,
+        deactivating_stake: 0,
+        transient_settle_epoch: 0,
+        commission,
+        commission_changed: false,
+        epoch_credits: 0,
+        last_vote_slot: 0,
+        last_scored_epoch: 0,
+        score_history: [0; SCORE_HISTORY_LEN],
+        admission_commission: commission,
+        quarantine_epoch: 0,
+        target_allocation_bps: (legacy.allocation_percentage as u16) * 100,
+        allocation_pinned: false,
+        concentration_tag: 0,
+        pool,
+        version: VALIDATOR_VERSION,
+        reserved: [0; RESERVED_SPACE],
+    }
+}
+
+/// Build the current pool layout from a v0 pool
+///
+/// Everything added since v0 gets the `StakingPool::initialize` defaults and
+/// the balances and validator count carry over. Validators still sit at the
+/// old address and the stake has no records yet, so both are marked as
+/// legacy until they are rebuilt.
+pub fn upgrade_legacy_pool(
+    legacy: &LegacyStakingPool,
+    fluid_sol_mint: Pubkey,
+    bump: u8,
+    clock: &Clock,
+) -> Result<StakingPool> {
+    let blank = vec![0u8; 8 + StakingPool::INIT_SPACE];
+    let mut pool = StakingPool::try_deserialize_unchecked(&mut &blank[..])?;
+    pool.initialize(legacy.authority, fluid_sol_mint, bump, clock);
+    pool.total_sol_deposited = legacy.total_sol_deposited;
+    pool.total_fluid_sol_minted = legacy.total_fluid_sol_minted;
+    pool.staked_sol_balance = legacy.staked_sol_balance;
+    pool.liquid_reserve = legacy.liquid_reserve;
+    pool.protocol_fees_earned = legacy.protocol_fees_earned;
+    pool.target_reserve_ratio = legacy.target_reserve_ratio;
+    pool.protocol_fee_bps = legacy.protocol_fee_bps;
+    pool.validator_count = legacy.validator_count;
+    pool.legacy_validators = legacy.validator_count;
+    pool.legacy_stake = legacy.staked_sol_balance;
+    pool.refresh_exchange_rate(clock)?;
+    Ok(pool)
+}
+
+/// Grow `account` to `new_len` (payer funds the extra rent) and load it as `T`
+pub fn grow_and_load<'info, T: AccountDeserialize + Discriminator>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_len: usize,
+) -> Result<T> {
+    require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidMigration);
+    {
+        let data = account.try_borrow_data()?;
+        require!(data.starts_with(T::DISCRIMINATOR), ErrorCode::InvalidMigration);
+    }
+
+    if account.data_len() < new_len {
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(account.lamports());
+        if rent_due > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: payer.clone(),
+                        to: account.clone(),
+                    },
+                ),
+                rent_due,
+            )?;
+        }
+        account.resize(new_len)?;
+    }
+
+    let data = account.try_borrow_data()?;
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Write an upgraded account back in place
+pub fn store<T: AccountSerialize>(account: &AccountInfo, value: &T) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    value.try_serialize(&mut &mut data[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AllocationMode, POOL_VERSION};
+
+    /// A pool exactly as the baseline program allocated and wrote it
+    fn baseline_pool(staked_sol_balance: u64) -> Vec<u8> {
+        let mut data = StakingPool::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(Pubkey::new_from_array([7; 32]).as_ref()); // authority
+        data.extend_from_slice(&12_000_000_000u64.to_le_bytes()); // total_sol_deposited
+        data.extend_from_slice(&10_000_000_000u64.to_le_bytes()); // total_fluid_sol_minted
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes()); // exchange_rate
+        data.extend_from_slice(&staked_sol_balance.to_le_bytes());
+        data.extend_from_slice(&11_000_000_000u64.to_le_bytes()); // liquid_reserve
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes()); // protocol_fees_earned
+        data.extend_from_slice(&[254, 2, 25]); // bump, validator_count, target_reserve_ratio
+        data.extend_from_slice(&500u16.to_le_bytes()); // protocol_fee_bps
+        data.resize(LEGACY_POOL_LEN, 0);
+        data
+    }
+
+    #[test]
+    fn migrates_baseline_pool() {
+        let mint = Pubkey::new_from_array([9; 32]);
+        let legacy = load_legacy_pool(&baseline_pool(0)).unwrap();
+        let pool = upgrade_legacy_pool(&legacy, mint, 253, &Clock::default()).unwrap();
+
+        assert_eq!(pool.authority, Pubkey::new_from_array([7; 32]));
+        assert_eq!(pool.fluid_sol_mint, mint);
+        assert_eq!(pool.bump, 253);
+        assert_eq!(pool.version, POOL_VERSION);
+        assert_eq!(pool.total_sol_deposited, 12_000_000_000);
+        assert_eq!(pool.total_fluid_sol_minted, 10_000_000_000);
+        assert_eq!(pool.exchange_rate, 1_200_000_000);
+        assert_eq!(pool.liquid_reserve, 11_000_000_000);
+        assert_eq!(pool.protocol_fees_earned, 1_000_000_000);
+        assert_eq!(pool.target_reserve_ratio, 25);
+        assert_eq!(pool.protocol_fee_bps, 500);
+        assert_eq!(pool.validator_count, 2);
+        assert_eq!(pool.legacy_validators, 2);
+        assert_eq!(pool.staked_sol_balance, 0);
+        assert_eq!(pool.legacy_stake, 0);
+
+        // Caps a zero-filled tail would have left at 0
+        let mut fresh = pool.clone();
+        fresh.initialize(pool.authority, mint, 253, &Clock::default());
+        assert_eq!(pool.max_validator_commission, fresh.max_validator_commission);
+        assert_eq!(pool.admission_max_commission, fresh.admission_max_commission);
+        assert_eq!(pool.max_validator_share_bps, fresh.max_validator_share_bps);
+        assert_eq!(pool.max_tag_share_bps, fresh.max_tag_share_bps);
+        assert_eq!(pool.max_rate_staleness_slots, fresh.max_rate_staleness_slots);
+        assert_ne!(pool.max_rate_staleness_slots, 0);
+        assert_ne!(pool.max_validator_share_bps, 0);
+        assert_ne!(pool.max_validator_commission, 0);
+        assert_eq!(pool.allocation_mode, AllocationMode::Static);
+    }
+
+    #[test]
+    fn carries_legacy_stake_over() {
+        let legacy = load_legacy_pool(&baseline_pool(4_000_000_000)).unwrap();
+        let pool = upgrade_legacy_pool(&legacy, Pubkey::new_unique(), 255, &Clock::default()).unwrap();
+
+        assert_eq!(pool.staked_sol_balance, 4_000_000_000);
+        assert_eq!(pool.legacy_stake, 4_000_000_000);
+        assert_eq!(pool.validator_count, 2);
+        assert_eq!(pool.exchange_rate, 1_200_000_000);
+    }
+
+    /// A validator exactly as the baseline program allocated and wrote it
+    fn baseline_validator() -> Vec<u8> {
+        let mut data = ValidatorInfo::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(Pubkey::new_from_array([3; 32]).as_ref()); // vote_account
+        data.push(40); // allocation_percentage
+        data.extend_from_slice(&2_000_000_000u64.to_le_bytes()); // total_delegated
+        data.extend_from_slice(&612u64.to_le_bytes()); // last_update_epoch
+        data.extend_from_slice(&[87, 1]); // performance_score, is_active
+        assert_eq!(data.len(), LEGACY_VALIDATOR_LEN);
+        data
+    }
+
+    #[test]
+    fn migrates_baseline_validator() {
+        let pool = Pubkey::new_from_array([5; 32]);
+        let legacy = load_legacy_validator(&baseline_validator()).unwrap();
+        let validator = upgrade_legacy_validator(&legacy, pool, 7);
+
+        assert_eq!(validator.vote_account, Pubkey::new_from_array([3; 32]));
+        assert_eq!(validator.allocation_percentage, 40);
+        assert_eq!(validator.last_update_epoch, 612);
+        assert_eq!(validator.performance_score, 87);
+        assert!(validator.is_active);
+        assert_eq!(validator.pool, pool);
+        assert_eq!(validator.version, VALIDATOR_VERSION);
+        assert_eq!(validator.total_delegated, 0);
+        assert_eq!(validator.target_allocation_bps, 4_000);
+        assert_eq!(validator.commission, 7);
+        assert_eq!(validator.admission_commission, 7);
+
+        // Re-serialized at the current size
+        let mut data = Vec::new();
+        validator.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + ValidatorInfo::INIT_SPACE);
+        let reloaded = ValidatorInfo::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(reloaded.pool, pool);
+        assert_eq!(reloaded.performance_score, 87);
+    }
+
+    #[test]
+    fn rejects_non_baseline_validator() {
+        let mut data = baseline_validator();
+        data.push(0);
+        assert!(load_legacy_validator(&data).is_err());
+
+        let mut data = baseline_validator();
+        data[..8].copy_from_slice(StakingPool::DISCRIMINATOR);
+        assert!(load_legacy_validator(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_non_baseline_data() {
+        let mut data = baseline_pool(0);
+        data.push(0);
+        assert!(load_legacy_pool(&data).is_err());
+
+        let mut data = baseline_pool(0);
+        data[0] ^= 1;
+        assert!(load_legacy_pool(&data).is_err());
+    }
+}
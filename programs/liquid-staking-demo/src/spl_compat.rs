@@ -0,0 +1,346 @@
+// ============================================================================
+// SPL STAKE-POOL COMPATIBILITY
+// ============================================================================
+//
+// Aggregators and wallets route SOL/stake into pools through the SPL
+// stake-pool instruction layout: a 1-byte tag followed by borsh arguments.
+// Those never match an Anchor discriminator, so they land in the program's
+// fallback and are mapped onto the native handlers here. Pools opt in with
+// `configure_spl_compat`.
+//
+// Account lists follow the SPL order. FluidSOL has no separate withdraw
+// authority or reserve stake: those slots must be the pool PDA. Deposited
+// stake is handed to the deposit authority PDA `[b"deposit_authority", pool]`
+// (never an authority of the pool's own stake), so pool stake accounts can't
+// be passed off as deposits. The SPL validator list slot takes the
+// ValidatorInfo instead, and the stake instructions need the
+// PoolStakeAccount record of the pool stake account appended after the SPL
+// accounts.
+//
+//   DepositSol (14, lamports: u64)
+//     0 pool, 1 pool, 2 pool, 3 depositor [signer], 4 fSOL destination,
+//     5 manager fee account, 6 referrer fSOL account, 7 fSOL mint,
+//     8 system program, 9 token program
+//
+//   WithdrawSol (16, fsol_amount: u64)
+//     0 pool, 1 pool, 2 fSOL owner [signer], 3 fSOL source, 4 pool,
+//     5 SOL destination, 6 manager fee account, 7 fSOL mint, 8 clock,
+//     9 stake history, 10 stake program, 11 token program
+//
+//   DepositStake (9)
+//     0 pool, 1 validator info, 2 deposit authority, 3 pool, 4 deposited
+//     stake account (staker and withdrawer set to the deposit authority),
+//     5 pool stake account, 6 pool, 7 fSOL destination, 8 manager fee
+//     account, 9 referrer fSOL account, 10 fSOL mint, 11 clock, 12 stake
+//     history, 13 token program, 14 stake program, 15 stake record of 5,
+//     16.. every ValidatorInfo when the validator is under a tag cap
+//
+//   WithdrawStake (10, fsol_amount: u64)
+//     0 pool, 1 validator info, 2 pool, 3 pool stake account to split,
+//     4 uninitialized stake account, 5 new staker/withdrawer, 6 fSOL owner
+//     [signer], 7 fSOL source, 8 manager fee account, 9 fSOL mint, 10 clock,
+//     11 token program, 12 stake program, 13 stake record of 3
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::math::{self, SafeMath};
+use crate::{
+    check_concentration, stake, withdraw_as_stake, withdraw_from_reserve, DepositSol, ErrorCode, PoolStakeAccount,
+    StakeAccountState, StakingPool, ValidatorInfo,
+};
+
+pub const DEPOSIT_STAKE: u8 = 9;
+pub const WITHDRAW_STAKE: u8 = 10;
+pub const DEPOSIT_SOL: u8 = 14;
+pub const WITHDRAW_SOL: u8 = 16;
+
+/// Dispatch an SPL stake-pool instruction
+pub fn process<'info>(program_id: &Pubkey, accounts: &'info [AccountInfo<'info>], data: &[u8]) -> Result<()> {
+    let (tag, args) = data
+        .split_first()
+        .ok_or(anchor_lang::error::ErrorCode::InstructionFallbackNotFound)?;
+    match *tag {
+        DEPOSIT_SOL => deposit_sol(program_id, accounts, read_amount(args)?),
+        WITHDRAW_SOL => withdraw_sol(program_id, accounts, read_amount(args)?),
+        DEPOSIT_STAKE => deposit_stake(program_id, accounts),
+        WITHDRAW_STAKE => withdraw_stake(program_id, accounts, read_amount(args)?),
+        _ => err!(anchor_lang::error::ErrorCode::InstructionFallbackNotFound),
+    }
+}
+
+fn deposit_sol<'info>(program_id: &Pubkey, accounts: &'info [AccountInfo<'info>], lamports: u64) -> Result<()> {
+    let pool = load_pool(program_id, accounts, &[0, 1, 2])?;
+    let user_fluid_sol_account = account(accounts, 4)?;
+    let referrer = account(accounts, 6)?;
+
+    let mut deposit = DepositSol {
+        user: Signer::try_from(account(accounts, 3)?)?,
+        fluid_sol_mint: load_mint(&pool, account(accounts, 7)?)?,
+        user_fluid_sol_account: InterfaceAccount::try_from(user_fluid_sol_account)?,
+        fee_token_account: load_fee_account(&pool, account(accounts, 5)?)?,
+        system_program: Program::try_from(account(accounts, 8)?)?,
+        token_program: Interface::try_from(account(accounts, 9)?)?,
+        pool,
+    };
+    // SPL clients pass the depositor's own account when there is no referrer
+    let referrer = (referrer.key() != user_fluid_sol_account.key()).then(|| referrer.clone());
+    deposit.process_deposit(lamports, referrer)?;
+    deposit.pool.exit(program_id)
+}
+
+fn withdraw_sol<'info>(program_id: &Pubkey, accounts: &'info [AccountInfo<'info>], fluid_sol_amount: u64) -> Result<()> {
+    let mut pool = load_pool(program_id, accounts, &[0, 1, 4])?;
+    let fluid_sol_mint = load_mint(&pool, account(accounts, 7)?)?;
+    withdraw_from_reserve(
+        &mut pool,
+        &fluid_sol_mint,
+        &InterfaceAccount::try_from(account(accounts, 3)?)?,
+        &Signer::try_from(account(accounts, 2)?)?.to_account_info(),
+        &Interface::try_from(account(accounts, 11)?)?,
+        fluid_sol_amount,
+        account(accounts, 5)?,
+    )?;
+    pool.exit(program_id)
+}
+
+fn deposit_stake<'info>(program_id: &Pubkey, accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+    let mut pool = load_pool(program_id, accounts, &[0, 3, 6])?;
+    let deposit_authority = account(accounts, 2)?;
+    let (expected_authority, deposit_authority_bump) =
+        Pubkey::find_program_address(&[b"deposit_authority", pool.key().as_ref()], program_id);
+    require_keys_eq!(deposit_authority.key(), expected_authority, ErrorCode::InvalidStakeAccount);
+    let mut validator_info = load_validator(&pool, account(accounts, 1)?)?;
+    let deposited_stake = account(accounts, 4)?;
+    let pool_stake = account(accounts, 5)?;
+    let fluid_sol_destination = account(accounts, 7)?;
+    let referrer = account(accounts, 9)?;
+    let fluid_sol_mint = load_mint(&pool, account(accounts, 10)?)?;
+    let clock = account(accounts, 11)?;
+    let token_program = Interface::<TokenInterface>::try_from(account(accounts, 13)?)?;
+    let mut stake_record = load_stake_record(&validator_info, pool_stake, account(accounts, 15)?)?;
+    let epoch = Clock::get()?.epoch;
+    require!(validator_info.is_active, ErrorCode::ValidatorInactive);
+
+    // The depositor handed both authorities to the deposit authority; the
+    // stake must be fully active with the same validator, unlocked
+    let deposited_state = stake::read_stake_state(deposited_stake)?;
+    let solana_stake_interface::state::StakeStateV2::Stake(meta, delegation_stake, _) = &deposited_state else {
+        return err!(ErrorCode::InvalidStakeAccount);
+    };
+    require!(
+        meta.authorized.staker == expected_authority
+            && meta.authorized.withdrawer == expected_authority
+            && !meta.lockup.is_in_force(&Clock::get()?, None)
+            && delegation_stake.delegation.voter_pubkey == validator_info.vote_account
+            && stake::stake_account_state(&deposited_state, epoch) == StakeAccountState::Active,
+        ErrorCode::InvalidStakeAccount
+    );
+
+    let pool_meta = stake::pool_stake_meta(pool_stake, &pool.key())?;
+    stake::untrack_transient(&mut pool, &mut validator_info, &stake_record)?;
+    stake::refresh_stake_record(&mut stake_record, &stake::read_stake_state(pool_stake)?, epoch);
+    require!(stake_record.state == StakeAccountState::Active, ErrorCode::IncompatibleStakeAccounts);
+    require!(
+        pool_stake.lamports().safe_sub(pool_meta.rent_exempt_reserve)? == stake_record.last_observed_lamports,
+        ErrorCode::UnharvestedRewards
+    );
+
+    // Every lamport of the deposited account, rent included, ends up delegated
+    let deposited = deposited_stake.lamports();
+    let fluid_sol_to_mint = math::fluid_sol_for_deposit(deposited, pool.total_sol_deposited, pool.total_fluid_sol_minted)?;
+    require!(fluid_sol_to_mint > 0, ErrorCode::InvalidAmount);
+
+    {
+        // Take the deposit over, then merge it into the pool's stake
+        let pool_key = pool.key();
+        let deposit_authority_seeds: &[&[u8]] = &[b"deposit_authority", pool_key.as_ref(), &[deposit_authority_bump]];
+        stake::authorize_all(deposited_stake, &pool_key, clock, deposit_authority, &[deposit_authority_seeds])?;
+
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        stake::merge(
+            pool_stake,
+            deposited_stake,
+            clock,
+            account(accounts, 12)?,
+            &pool.to_account_info(),
+            pool_signer,
+        )?;
+    }
+
+    // Same fee split as a SOL deposit
+    let deposit_fee = math::bps_of(fluid_sol_to_mint, pool.deposit_fee_bps as u64)?;
+    let referral_fee = if referrer.key() != fluid_sol_destination.key() {
+        math::percent_of(deposit_fee, pool.referral_fee_percent as u64)?
+    } else {
+        0
+    };
+    let manager_fee = deposit_fee.safe_sub(referral_fee)?;
+    mint_fluid_sol(&pool, &fluid_sol_mint, &token_program, fluid_sol_destination, fluid_sol_to_mint.safe_sub(deposit_fee)?)?;
+    if manager_fee > 0 {
+        let fee_account = load_fee_account(&pool, account(accounts, 8)?)?.ok_or(ErrorCode::MissingFeeAccounts)?;
+        mint_fluid_sol(&pool, &fluid_sol_mint, &token_program, &fee_account.to_account_info(), manager_fee)?;
+    }
+    if referral_fee > 0 {
+        mint_fluid_sol(&pool, &fluid_sol_mint, &token_program, referrer, referral_fee)?;
+    }
+
+    stake_record.principal = stake_record.principal.safe_add(deposited)?;
+    stake_record.last_observed_lamports = stake_record.last_observed_lamports.safe_add(deposited)?;
+    stake::track_transient(&mut pool, &mut validator_info, &stake_record)?;
+    validator_info.total_delegated = validator_info.total_delegated.safe_add(deposited)?;
+    pool.total_sol_deposited = pool.total_sol_deposited.safe_add(deposited)?;
+    pool.total_fluid_sol_minted = pool.total_fluid_sol_minted.safe_add(fluid_sol_to_mint)?;
+    pool.staked_sol_balance = pool.staked_sol_balance.safe_add(deposited)?;
+    pool.refresh_exchange_rate(&Clock::get()?)?;
+    check_concentration(&pool, &validator_info, &[], accounts.get(16..).unwrap_or(&[]))?;
+
+    msg!("Stake deposit of {} SOL merged into {}",
+         deposited as f64 / 1_000_000_000.0,
+         pool_stake.key());
+
+    pool.exit(program_id)?;
+    validator_info.exit(program_id)?;
+    stake_record.exit(program_id)
+}
+
+fn withdraw_stake<'info>(program_id: &Pubkey, accounts: &'info [AccountInfo<'info>], fluid_sol_amount: u64) -> Result<()> {
+    let mut pool = load_pool(program_id, accounts, &[0, 2])?;
+    let mut validator_info = load_validator(&pool, account(accounts, 1)?)?;
+    let pool_stake = account(accounts, 3)?;
+    let split_stake = account(accounts, 4)?;
+    let new_authority = account(accounts, 5)?.key();
+    let fluid_sol_owner = Signer::try_from(account(accounts, 6)?)?;
+    let fluid_sol_source = InterfaceAccount::<TokenAccount>::try_from(account(accounts, 7)?)?;
+    let fluid_sol_mint = load_mint(&pool, account(accounts, 9)?)?;
+    let clock = account(accounts, 10)?;
+    let token_program = Interface::<TokenInterface>::try_from(account(accounts, 11)?)?;
+    let mut stake_record = load_stake_record(&validator_info, pool_stake, account(accounts, 13)?)?;
+
+    let sol_to_return = withdraw_as_stake(
+        &mut pool,
+        &mut validator_info,
+        &mut stake_record,
+        pool_stake,
+        split_stake,
+        CpiContext::new(
+            token_program.to_account_info(),
+            anchor_spl::token_interface::Burn {
+                mint: fluid_sol_mint.to_account_info(),
+                from: fluid_sol_source.to_account_info(),
+                authority: fluid_sol_owner.to_account_info(),
+            },
+        ),
+        fluid_sol_amount,
+    )?;
+    {
+        let pool_seeds = pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        stake::authorize_all(split_stake, &new_authority, clock, &pool.to_account_info(), pool_signer)?;
+    }
+
+    msg!("Withdrew {} fSOL as a {} SOL stake account {}",
+         fluid_sol_amount as f64 / 1_000_000_000.0,
+         sol_to_return as f64 / 1_000_000_000.0,
+         split_stake.key());
+
+    pool.exit(program_id)?;
+    validator_info.exit(program_id)?;
+    stake_record.exit(program_id)
+}
+
+fn read_amount(args: &[u8]) -> Result<u64> {
+    let bytes = args.get(..8).ok_or(ErrorCode::InvalidAmount)?;
+    Ok(u64::from_le_bytes(bytes.try_into().map_err(|_| error!(ErrorCode::InvalidAmount))?))
+}
+
+fn account<'info>(accounts: &'info [AccountInfo<'info>], index: usize) -> Result<&'info AccountInfo<'info>> {
+    accounts
+        .get(index)
+        .ok_or_else(|| error!(anchor_lang::error::ErrorCode::AccountNotEnoughKeys))
+}
+
+/// Load the pool from `slots[0]` and check every slot holds it, as the pool
+/// stands in for the SPL withdraw authority, reserve and deposit authority
+fn load_pool<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    slots: &[usize],
+) -> Result<Account<'info, StakingPool>> {
+    let pool = Account::<StakingPool>::try_from(account(accounts, slots[0])?)?;
+    let expected = Pubkey::create_program_address(&pool.signer_seeds(), program_id)
+        .map_err(|_| error!(ErrorCode::WrongPool))?;
+    require_keys_eq!(pool.key(), expected, ErrorCode::WrongPool);
+    for slot in &slots[1..] {
+        require_keys_eq!(account(accounts, *slot)?.key(), expected, ErrorCode::WrongPool);
+    }
+    require!(pool.spl_compat_enabled, ErrorCode::SplCompatDisabled);
+    Ok(pool)
+}
+
+fn load_mint<'info>(pool: &StakingPool, info: &'info AccountInfo<'info>) -> Result<InterfaceAccount<'info, Mint>> {
+    require_keys_eq!(info.key(), pool.fluid_sol_mint, ErrorCode::InvalidMint);
+    InterfaceAccount::try_from(info)
+}
+
+/// The SPL manager fee slot, which must be the pool's fee account (the
+/// default address, i.e. the system program, while none is configured)
+fn load_fee_account<'info>(
+    pool: &StakingPool,
+    info: &'info AccountInfo<'info>,
+) -> Result<Option<InterfaceAccount<'info, TokenAccount>>> {
+    require_keys_eq!(info.key(), pool.fee_token_account, ErrorCode::InvalidFeeRecipient);
+    if pool.fee_token_account == Pubkey::default() {
+        return Ok(None);
+    }
+    Ok(Some(InterfaceAccount::try_from(info)?))
+}
+
+fn load_validator<'info>(
+    pool: &Account<'info, StakingPool>,
+    info: &'info AccountInfo<'info>,
+) -> Result<Account<'info, ValidatorInfo>> {
+    let validator_info = Account::<ValidatorInfo>::try_from(info)?;
+    require_keys_eq!(validator_info.pool, pool.key(), ErrorCode::WrongPool);
+    Ok(validator_info)
+}
+
+fn load_stake_record<'info>(
+    validator_info: &Account<'info, ValidatorInfo>,
+    stake_account: &AccountInfo<'info>,
+    info: &'info AccountInfo<'info>,
+) -> Result<Account<'info, PoolStakeAccount>> {
+    let stake_record = Account::<PoolStakeAccount>::try_from(info)?;
+    let expected = Pubkey::create_program_address(
+        &[b"stake_record", stake_account.key().as_ref(), &[stake_record.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| error!(ErrorCode::InvalidStakeAccount))?;
+    require_keys_eq!(info.key(), expected, ErrorCode::InvalidStakeAccount);
+    require_keys_eq!(stake_record.validator, validator_info.key(), ErrorCode::InvalidStakeAccount);
+    Ok(stake_record)
+}
+
+fn mint_fluid_sol<'info>(
+    pool: &Account<'info, StakingPool>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = pool.signer_seeds();
+    let signer = &[&seeds[..]];
+    anchor_spl::token_interface::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            anchor_spl::token_interface::MintTo {
+                mint: mint.to_account_info(),
+                to: to.clone(),
+                authority: pool.to_account_info(),
+            },
+            signer,
+        ),
+        amount,
+    )
+}
@@ -0,0 +1,232 @@
+// ============================================================================
+// STAKE ACCOUNT HELPERS
+// ============================================================================
+//
+// Reading native stake accounts the pool owns, and the stake program CPIs the
+// pool PDA signs as staker/withdrawer. Stake accounts are passed in as
+// unchecked AccountInfos, so reads validate the owner program and the pool's
+// withdraw authority before trusting the data.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use solana_stake_interface as stake;
+use solana_stake_interface::instruction::StakeInstruction;
+use solana_stake_interface::state::{Meta, StakeAuthorize, StakeStateV2};
+
+use crate::math::SafeMath;
+use crate::{ErrorCode, PoolStakeAccount, StakeAccountState, StakingPool, ValidatorInfo};
+
+/// Deserialize a native stake account
+pub fn read_stake_state(stake_account: &AccountInfo) -> Result<StakeStateV2> {
+    require_keys_eq!(*stake_account.owner, stake::program::ID, ErrorCode::InvalidStakeAccount);
+    let data = stake_account.try_borrow_data()?;
+    StakeStateV2::deserialize(&mut &data[..]).map_err(|_| error!(ErrorCode::InvalidStakeAccount))
+}
+
+/// Metadata of a stake account the pool is the withdraw authority of
+pub fn pool_stake_meta(stake_account: &AccountInfo, pool: &Pubkey) -> Result<Meta> {
+    let meta = match read_stake_state(stake_account)? {
+        StakeStateV2::Initialized(meta) | StakeStateV2::Stake(meta, _, _) => meta,
+        _ => return err!(ErrorCode::InvalidStakeAccount),
+    };
+    require_keys_eq!(meta.authorized.withdrawer, *pool, ErrorCode::InvalidStakeAccount);
+    Ok(meta)
+}
+
+/// Lifecycle state of a stake account at `epoch`
+pub fn stake_account_state(state: &StakeStateV2, epoch: u64) -> StakeAccountState {
+    let StakeStateV2::Stake(_, stake, _) = state else {
+        return StakeAccountState::Inactive;
+    };
+    let delegation = &stake.delegation;
+    if delegation.deactivation_epoch == u64::MAX {
+        if delegation.activation_epoch >= epoch {
+            StakeAccountState::Activating
+        } else {
+            StakeAccountState::Active
+        }
+    } else if delegation.deactivation_epoch >= epoch {
+        StakeAccountState::Deactivating
+    } else {
+        StakeAccountState::Inactive
+    }
+}
+
+/// Sync a PoolStakeAccount's epochs and state with the on-chain stake account
+pub fn refresh_stake_record(record: &mut PoolStakeAccount, state: &StakeStateV2, epoch: u64) {
+    if let StakeStateV2::Stake(_, stake, _) = state {
+        record.activation_epoch = stake.delegation.activation_epoch;
+        record.deactivation_epoch = stake.delegation.deactivation_epoch;
+    }
+    record.state = stake_account_state(state, epoch);
+}
+
+/// Remove a record's staked lamports from its validator's and the pool's transient totals
+///
+/// Call before a record's state or lamports change, then `track_transient`
+/// afterwards, so the totals always equal the sum over Activating and
+/// Deactivating records.
+pub fn untrack_transient(
+    pool: &mut StakingPool,
+    validator: &mut ValidatorInfo,
+    record: &PoolStakeAccount,
+) -> Result<()> {
+    let lamports = record.staked_lamports()?;
+    match record.state {
+        StakeAccountState::Activating => {
+            validator.activating_stake = validator.activating_stake.safe_sub(lamports)?;
+            pool.activating_sol = pool.activating_sol.safe_sub(lamports)?;
+        }
+        StakeAccountState::Deactivating => {
+            validator.deactivating_stake = validator.deactivating_stake.safe_sub(lamports)?;
+            pool.deactivating_sol = pool.deactivating_sol.safe_sub(lamports)?;
+        }
+        StakeAccountState::Active | StakeAccountState::Inactive => {}
+    }
+    if validator.activating_stake == 0 && validator.deactivating_stake == 0 {
+        validator.transient_settle_epoch = 0;
+    }
+    Ok(())
+}
+
+/// Add a record's staked lamports to the transient totals and push out the validator's settle epoch
+pub fn track_transient(
+    pool: &mut StakingPool,
+    validator: &mut ValidatorInfo,
+    record: &PoolStakeAccount,
+) -> Result<()> {
+    let lamports = record.staked_lamports()?;
+    let settle_epoch = match record.state {
+        StakeAccountState::Activating => {
+            validator.activating_stake = validator.activating_stake.safe_add(lamports)?;
+            pool.activating_sol = pool.activating_sol.safe_add(lamports)?;
+            record.activation_epoch.safe_add(1)?
+        }
+        StakeAccountState::Deactivating => {
+            validator.deactivating_stake = validator.deactivating_stake.safe_add(lamports)?;
+            pool.deactivating_sol = pool.deactivating_sol.safe_add(lamports)?;
+            record.deactivation_epoch.safe_add(1)?
+        }
+        StakeAccountState::Active | StakeAccountState::Inactive => return Ok(()),
+    };
+    validator.transient_settle_epoch = validator.transient_settle_epoch.max(settle_epoch);
+    Ok(())
+}
+
+/// Split `lamports` into `destination`, already allocated and assigned to the stake program
+pub fn split<'info>(
+    source: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    pool_signer: &[&[&[u8]]],
+    lamports: u64,
+) -> Result<()> {
+    let split_ix = Instruction::new_with_bincode(
+        stake::program::ID,
+        &StakeInstruction::Split(lamports),
+        vec![
+            AccountMeta::new(source.key(), false),
+            AccountMeta::new(destination.key(), false),
+            AccountMeta::new_readonly(pool.key(), true),
+        ],
+    );
+    invoke_signed(
+        &split_ix,
+        &[source.clone(), destination.clone(), pool.clone()],
+        pool_signer,
+    )?;
+    Ok(())
+}
+
+/// Deactivate a pool stake account
+pub fn deactivate<'info>(
+    stake_account: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    pool_signer: &[&[&[u8]]],
+) -> Result<()> {
+    let deactivate_ix = stake::instruction::deactivate_stake(&stake_account.key(), &pool.key());
+    invoke_signed(
+        &deactivate_ix,
+        &[stake_account.clone(), clock.clone(), pool.clone()],
+        pool_signer,
+    )?;
+    Ok(())
+}
+
+/// Delegate a pool stake account to `vote_account`
+pub fn delegate<'info>(
+    stake_account: &AccountInfo<'info>,
+    vote_account: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    stake_history: &AccountInfo<'info>,
+    stake_config: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    pool_signer: &[&[&[u8]]],
+) -> Result<()> {
+    let delegate_ix = stake::instruction::delegate_stake(&stake_account.key(), &pool.key(), &vote_account.key());
+    invoke_signed(
+        &delegate_ix,
+        &[
+            stake_account.clone(),
+            vote_account.clone(),
+            clock.clone(),
+            stake_history.clone(),
+            stake_config.clone(),
+            pool.clone(),
+        ],
+        pool_signer,
+    )?;
+    Ok(())
+}
+
+/// Merge `source` into `destination`, both pool stake accounts
+pub fn merge<'info>(
+    destination: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    stake_history: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    pool_signer: &[&[&[u8]]],
+) -> Result<()> {
+    for ix in stake::instruction::merge(&destination.key(), &source.key(), &pool.key()).iter() {
+        invoke_signed(
+            ix,
+            &[
+                destination.clone(),
+                source.clone(),
+                clock.clone(),
+                stake_history.clone(),
+                pool.clone(),
+            ],
+            pool_signer,
+        )?;
+    }
+    Ok(())
+}
+
+/// Hand both authorities of a stake account held by a program PDA over to `new_authority`
+pub fn authorize_all<'info>(
+    stake_account: &AccountInfo<'info>,
+    new_authority: &Pubkey,
+    clock: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    authority_signer: &[&[&[u8]]],
+) -> Result<()> {
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        let authorize_ix = stake::instruction::authorize(
+            &stake_account.key(),
+            &authority.key(),
+            new_authority,
+            stake_authorize,
+            None,
+        );
+        invoke_signed(
+            &authorize_ix,
+            &[stake_account.clone(), clock.clone(), authority.clone()],
+            authority_signer,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// ============================================================================
+// VOTE ACCOUNT HELPERS
+// ============================================================================
+//
+// Minimal reader for native vote accounts. Fully deserializing VoteState is
+// expensive on-chain, so this walks the bincode layout (V1_14_11 and Current)
+// and keeps only what validator scoring needs.
+
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+const VERSION_V1_14_11: u32 = 1;
+const VERSION_CURRENT: u32 = 2;
+const PRIOR_VOTERS_LEN: usize = 32;
+
+/// What the pool reads from a validator's vote account
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoteSummary {
+    pub commission: u8,
+    pub last_vote_slot: u64,
+    pub epoch_credits: Vec<(u64, u64, u64)>, // (epoch, credits, prev_credits)
+}
+
+impl VoteSummary {
+    /// Vote credits earned during `epoch` (0 if the validator didn't vote)
+    pub fn credits_in_epoch(&self, epoch: u64) -> u64 {
+        self.epoch_credits
+            .iter()
+            .find(|(e, _, _)| *e == epoch)
+            .map(|(_, credits, prev)| credits.saturating_sub(*prev))
+            .unwrap_or(0)
+    }
+}
+
+/// Parse a vote account owned by the vote program
+pub fn read_vote_summary(vote_account: &AccountInfo) -> Result<VoteSummary> {
+    require_keys_eq!(
+        *vote_account.owner,
+        anchor_lang::solana_program::vote::program::ID,
+        ErrorCode::InvalidVoteAccount
+    );
+    let data = vote_account.try_borrow_data()?;
+    parse_vote_summary(&data).ok_or_else(|| error!(ErrorCode::InvalidVoteAccount))
+}
+
+fn parse_vote_summary(data: &[u8]) -> Option<VoteSummary> {
+    let mut reader = Reader { data, offset: 0 };
+
+    let vote_len = match reader.u32()? {
+        VERSION_V1_14_11 => 8 + 4,     // Lockout { slot, confirmation_count }
+        VERSION_CURRENT => 1 + 8 + 4,  // LandedVote { latency, lockout }
+        _ => return None,
+    };
+    reader.skip(32 + 32)?; // node_pubkey, authorized_withdrawer
+    let commission = reader.u8()?;
+
+    let votes = reader.len()?;
+    let mut last_vote_slot = 0;
+    for _ in 0..votes {
+        let vote = reader.take(vote_len)?;
+        let slot_offset = vote_len - 12;
+        last_vote_slot = u64::from_le_bytes(vote[slot_offset..slot_offset + 8].try_into().ok()?);
+    }
+
+    if reader.u8()? == 1 {
+        reader.skip(8)?; // root_slot
+    }
+    let authorized_voters = reader.len()?;
+    reader.skip(authorized_voters.checked_mul(8 + 32)?)?;
+    reader.skip(PRIOR_VOTERS_LEN * (32 + 8 + 8) + 8 + 1)?;
+
+    let entries = reader.len()?;
+    let mut epoch_credits = Vec::with_capacity(entries.min(64));
+    for _ in 0..entries {
+        epoch_credits.push((reader.u64()?, reader.u64()?, reader.u64()?));
+    }
+
+    Some(VoteSummary { commission, last_vote_slot, epoch_credits })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(len)?;
+        let bytes = self.data.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote_account_data(version: u32, commission: u8, vote_slots: &[u64], credits: &[(u64, u64, u64)]) -> Vec<u8> {
+        let mut data = version.to_le_bytes().to_vec();
+        data.extend([1u8; 64]);
+        data.push(commission);
+        data.extend((vote_slots.len() as u64).to_le_bytes());
+        for slot in vote_slots {
+            if version == VERSION_CURRENT {
+                data.push(0);
+            }
+            data.extend(slot.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+        }
+        data.push(1);
+        data.extend(7u64.to_le_bytes());
+        data.extend(1u64.to_le_bytes());
+        data.extend([2u8; 40]);
+        data.extend(vec![0u8; PRIOR_VOTERS_LEN * 48 + 8 + 1]);
+        data.extend((credits.len() as u64).to_le_bytes());
+        for (epoch, credits, prev) in credits {
+            data.extend(epoch.to_le_bytes());
+            data.extend(credits.to_le_bytes());
+            data.extend(prev.to_le_bytes());
+        }
+        data.extend([0u8; 16]);
+        data
+    }
+
+    #[test]
+    fn parses_both_supported_versions() {
+        for version in [VERSION_V1_14_11, VERSION_CURRENT] {
+            let data = vote_account_data(version, 8, &[100, 101, 105], &[(3, 1_000, 0), (4, 2_500, 1_000)]);
+            let summary = parse_vote_summary(&data).unwrap();
+            assert_eq!(summary.commission, 8);
+            assert_eq!(summary.last_vote_slot, 105);
+            assert_eq!(summary.credits_in_epoch(4), 1_500);
+            assert_eq!(summary.credits_in_epoch(5), 0);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_unknown_layouts() {
+        let data = vote_account_data(VERSION_CURRENT, 5, &[1], &[(1, 10, 0)]);
+        assert!(parse_vote_summary(&data[..data.len() - 40]).is_none());
+        assert!(parse_vote_summary(&vote_account_data(0, 5, &[], &[])).is_none());
+    }
+}
@@ -11,6 +11,7 @@ import {
     createAssociatedTokenAccount,
     getAccount as getTokenAccount,
     getAssociatedTokenAddress,
+  TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import * as fs from "fs";
 import * as os from "os";
@@ -138,7 +139,7 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
 
         // Find PDAs
         [pool] = PublicKey.findProgramAddressSync(
-            [Buffer.from("pool")],
+            [Buffer.from("pool"), fluidSOLMint.publicKey.toBuffer()],
             program.programId,
         );
 
@@ -162,6 +163,7 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
                 .accounts({
                     authority: authority.publicKey,
                     fluidSolMint: fluidSOLMint.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
                 })
                 .signers([authority, fluidSOLMint])
                 .rpc();
@@ -212,6 +214,7 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
                     authority: authority.publicKey,
                     pool: pool,
                     validatorInfo: validatorInfo,
+                    voteAccount: realValidatorVote,
                 })
                 .signers([authority])
                 .rpc();
@@ -265,8 +268,10 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
             const tx = await program.methods
                 .depositSol(new anchor.BN(depositAmount))
                 .accounts({
+                    pool: pool,
                     user: user.publicKey,
                     fluidSolMint: fluidSOLMint.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
                     userFluidSolAccount: userFluidSOLAccount,
                 })
                 .signers([user])
@@ -350,16 +355,12 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
                 "StakeConfig11111111111111111111111111111111",
             );
 
-            const clock = await program.provider.connection.getSlot();
-
             // PERFORM REAL STAKING! 🔥
             try {
                 const tx = await program.methods
-                    .stakeToValidator(
-                        new anchor.BN(stakeAmount),
-                        new anchor.BN(clock),
-                    )
+                    .stakeToValidator(new anchor.BN(stakeAmount))
                     .accounts({
+                        pool: pool,
                         authority: authority.publicKey,
                         validatorInfo: validatorInfo,
                         validatorVoteAccount: realValidatorVote,
@@ -417,8 +418,35 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
 
             console.log("✅ All accounting verified!");
             console.log("💰 Stake will be ACTIVE in next epoch (~2-3 days)");
+
+            // Stake account and its registry record are derivable from on-chain state
+            const [stakeAccountPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("stake"),
+                    pool.toBuffer(),
+                    realValidatorVote.toBuffer(),
+                    new anchor.BN(validatorBefore.nextStakeIndex).toArrayLike(
+                        Buffer,
+                        "le",
+                        4,
+                    ),
+                ],
+                program.programId,
+            );
+            const [stakeRecord] = PublicKey.findProgramAddressSync(
+                [Buffer.from("stake_record"), stakeAccountPda.toBuffer()],
+                program.programId,
+            );
+            const record = await program.account.poolStakeAccount.fetch(
+                stakeRecord,
+            );
+            assert.equal(record.principal.toNumber(), stakeAmount);
+            assert.equal(
+                validatorAfter.nextStakeIndex,
+                validatorBefore.nextStakeIndex + 1,
+            );
             console.log(
-                `🎯 Stake account TO LOG!: ${stakeAccount.publicKey.toString()}`,
+                `🎯 Stake account TO LOG!: ${stakeAccountPda.toString()}`,
             );
         });
 
@@ -441,12 +469,11 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
             const stakeConfigAccount = new PublicKey(
                 "StakeConfig11111111111111111111111111111111",
             );
-            const clock = await program.provider.connection.getSlot();
-
             try {
                 await program.methods
-                    .stakeToValidator(new anchor.BN(excessiveAmount), new anchor.BN(clock),)
+                    .stakeToValidator(new anchor.BN(excessiveAmount))
                     .accounts({
+                        pool: pool,
                         authority: authority.publicKey,
                         validatorInfo: validatorInfo,
                         validatorVoteAccount: realValidatorVote,
@@ -482,6 +509,7 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
             const tx = await program.methods
                 .updateRewards(new anchor.BN(rewardsEarned))
                 .accounts({
+                    pool: pool,
                     authority: authority.publicKey,
                 })
                 .signers([authority])
@@ -526,8 +554,10 @@ describe("🚀 INTEGRATION TESTS - Real Validator Staking", () => {
             const tx = await program.methods
                 .withdrawSol(new anchor.BN(withdrawAmount)) // instant withdrawal
                 .accounts({
+                    pool: pool,
                     user: user.publicKey,
                     fluidSolMint: fluidSOLMint.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
                     userFluidSolAccount: userFluidSOLAccount,
                 })
                 .signers([user])
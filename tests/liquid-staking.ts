@@ -1,13 +1,23 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { LiquidStaking } from "../target/types/liquid_staking";
-import { Keypair, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import { FluidsolConsumer } from "../target/types/fluidsol_consumer";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SYSVAR_STAKE_HISTORY_PUBKEY,
+} from "@solana/web3.js";
 import { assert } from "chai";
 import {
   createAssociatedTokenAccount,
   getAccount as getTokenAccount,
   getAssociatedTokenAddress,
+  getTokenMetadata,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
+import { verifyPool } from "./utils/verify-pool";
 
 describe("liquid-staking", () => {
   // Configure the client to use the local cluster
@@ -23,7 +33,7 @@ describe("liquid-staking", () => {
   let userFluidSOLAccount: PublicKey;
   let poolBump: number;
 
-  let validatorVoteAccount: Keypair;
+  let validatorVoteAccount: PublicKey;
   let validatorInfo: PublicKey;
   let stakeAccount1: Keypair;
 
@@ -45,9 +55,12 @@ describe("liquid-staking", () => {
     authority = Keypair.generate();
     user = Keypair.generate();
     fluidSOLMint = Keypair.generate();
-    validatorVoteAccount = Keypair.generate();
     stakeAccount1 = Keypair.generate();
 
+    // add_validator reads the commission from a real vote account
+    const voteAccounts = await provider.connection.getVoteAccounts();
+    validatorVoteAccount = new PublicKey(voteAccounts.current[0].votePubkey);
+
     // Airdrop SOL to test accounts
     await provider.connection.confirmTransaction(
       await provider.connection.requestAirdrop(
@@ -64,7 +77,7 @@ describe("liquid-staking", () => {
 
     // Find PDA addresses
     [pool, poolBump] = PublicKey.findProgramAddressSync(
-      [Buffer.from("pool"), authority.publicKey.toBuffer()],
+      [Buffer.from("pool"), fluidSOLMint.publicKey.toBuffer()],
       program.programId,
     );
 
@@ -82,6 +95,7 @@ describe("liquid-staking", () => {
         .accounts({
           authority: authority.publicKey,
           fluidSolMint: fluidSOLMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([authority, fluidSOLMint])
         .rpc();
@@ -121,9 +135,11 @@ describe("liquid-staking", () => {
       const tx = await program.methods
         .depositSol(new anchor.BN(depositAmount))
         .accounts({
+          pool: pool,
           user: user.publicKey,
           authority: authority.publicKey,
           fluidSolMint: fluidSOLMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
           userFluidSolAccount: userFluidSOLAccount,
         })
         .signers([user])
@@ -155,9 +171,11 @@ describe("liquid-staking", () => {
         await program.methods
           .depositSol(new anchor.BN(smallAmount))
           .accounts({
+            pool: pool,
             user: user.publicKey,
             authority: authority.publicKey,
             fluidSolMint: fluidSOLMint.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
             userFluidSolAccount: userFluidSOLAccount,
           })
           .signers([user])
@@ -181,9 +199,11 @@ describe("liquid-staking", () => {
       const tx = await program.methods
         .withdrawSol(new anchor.BN(withdrawAmount), true) // instant = true
         .accounts({
+          pool: pool,
           user: user.publicKey,
           authority: authority.publicKey,
           fluidSolMint: fluidSOLMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
           userFluidSolAccount: userFluidSOLAccount,
         })
         .signers([user])
@@ -220,9 +240,11 @@ describe("liquid-staking", () => {
         await program.methods
           .withdrawSol(new anchor.BN(largeAmount), true)
           .accounts({
+            pool: pool,
             user: user.publicKey,
             authority: authority.publicKey,
             fluidSolMint: fluidSOLMint.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
             userFluidSolAccount: userFluidSOLAccount,
           })
           .signers([user])
@@ -245,11 +267,12 @@ describe("liquid-staking", () => {
       );
 
       const tx = await program.methods
-        .addValidator(validatorVoteAccount.publicKey, allocation)
+        .addValidator(validatorVoteAccount, allocation)
         .accounts({
           authority: authority.publicKey,
           pool: pool,
           validatorInfo: validatorInfo,
+          voteAccount: validatorVoteAccount,
         })
         .signers([authority])
         .rpc();
@@ -265,7 +288,7 @@ describe("liquid-staking", () => {
       );
       assert.equal(
         validatorAccount.voteAccount.toString(),
-        validatorVoteAccount.publicKey.toString(),
+        validatorVoteAccount.toString(),
       );
       assert.equal(validatorAccount.allocationPercentage, allocation);
       assert.equal(validatorAccount.isActive, true);
@@ -285,6 +308,7 @@ describe("liquid-staking", () => {
             authority: user.publicKey,
             pool: pool,
             validatorInfo,
+            voteAccount: validatorVote,
           })
           .signers([user])
           .rpc();
@@ -307,6 +331,7 @@ describe("liquid-staking", () => {
       await program.methods
         .updateRewards(new anchor.BN(rewardsEarned))
         .accounts({
+          pool: pool,
           authority: authority.publicKey,
         })
         .signers([authority])
@@ -340,6 +365,7 @@ describe("liquid-staking", () => {
       const tx = await program.methods
         .withdrawProtocolFees(feesToWithdraw)
         .accounts({
+          pool: pool,
           authority: authority.publicKey,
         })
         .signers([authority])
@@ -357,4 +383,1287 @@ describe("liquid-staking", () => {
       assert.equal(poolAccountAfter.protocolFeesEarned.toNumber(), 0);
     });
   });
+
+  describe("7. Protocol Fees as fSOL", () => {
+    let feeTokenAccount: PublicKey;
+
+    it("Should mint reward fees as fSOL to the fee account", async () => {
+      feeTokenAccount = await createAssociatedTokenAccount(
+        provider.connection,
+        authority,
+        fluidSOLMint.publicKey,
+        authority.publicKey,
+      );
+
+      await program.methods
+        .configureProtocolFees(true)
+        .accounts({
+          pool: pool,
+          authority: authority.publicKey,
+          fluidSolMint: fluidSOLMint.publicKey,
+          feeTokenAccount,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolBefore = await program.account.stakingPool.fetch(pool);
+      const rewardsEarned = 0.1 * LAMPORTS_PER_SOL;
+
+      await program.methods
+        .updateRewards(new anchor.BN(rewardsEarned))
+        .accounts({
+          pool: pool,
+          authority: authority.publicKey,
+          fluidSolMint: fluidSOLMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          feeTokenAccount,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolAfter = await program.account.stakingPool.fetch(pool);
+
+      // Whole reward stays as backing, no lamports set aside for the protocol
+      assert.equal(
+        poolAfter.totalSolDeposited.toNumber(),
+        poolBefore.totalSolDeposited.toNumber() + rewardsEarned,
+      );
+      assert.equal(
+        poolAfter.protocolFeesEarned.toNumber(),
+        poolBefore.protocolFeesEarned.toNumber(),
+      );
+
+      const feeAccount = await getTokenAccount(
+        provider.connection,
+        feeTokenAccount,
+      );
+      assert.isAbove(Number(feeAccount.amount), 0);
+      assert.equal(
+        poolAfter.totalFluidSolMinted.toNumber(),
+        poolBefore.totalFluidSolMinted.toNumber() + Number(feeAccount.amount),
+      );
+    });
+
+    it("Should reject fSOL fee rewards without the fee accounts", async () => {
+      try {
+        await program.methods
+          .updateRewards(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+          .accounts({
+            pool: pool,
+            authority: authority.publicKey,
+            fluidSolMint: null,
+            feeTokenAccount: null,
+            tokenProgram: null,
+          })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have failed with missing fee accounts");
+      } catch (err) {
+        assert.include(err.toString(), "MissingFeeAccounts");
+      }
+    });
+  });
+
+  describe("8. Deposit Fees & Referrals", () => {
+    it("Should pay the referrer part of the deposit fee", async () => {
+      const referrer = Keypair.generate();
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(
+          referrer.publicKey,
+          LAMPORTS_PER_SOL,
+        ),
+      );
+      const referrerFluidSolAccount = await createAssociatedTokenAccount(
+        provider.connection,
+        referrer,
+        fluidSOLMint.publicKey,
+        referrer.publicKey,
+      );
+      const feeTokenAccount = await getAssociatedTokenAddress(
+        fluidSOLMint.publicKey,
+        authority.publicKey,
+      );
+
+      await program.methods
+        .configureDepositFees(100, 50) // 1% deposit fee, half to referrers
+        .accounts({ pool, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      await program.methods
+        .depositSolWithReferral(new anchor.BN(LAMPORTS_PER_SOL))
+        .accounts({
+          deposit: {
+            pool: pool,
+            user: user.publicKey,
+            fluidSolMint: fluidSOLMint.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            userFluidSolAccount: userFluidSOLAccount,
+            feeTokenAccount,
+          },
+          referrerFluidSolAccount,
+        })
+        .signers([user])
+        .rpc();
+
+      const [referralRecord] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("referral"),
+          pool.toBuffer(),
+          referrerFluidSolAccount.toBuffer(),
+        ],
+        program.programId,
+      );
+      const record = await program.account.referralRecord.fetch(
+        referralRecord,
+      );
+      assert.equal(record.referralCount.toNumber(), 1);
+      assert.equal(record.totalSolReferred.toNumber(), LAMPORTS_PER_SOL);
+
+      const referrerAccount = await getTokenAccount(
+        provider.connection,
+        referrerFluidSolAccount,
+      );
+      assert.equal(
+        referrerAccount.amount.toString(),
+        record.totalFeesEarned.toString(),
+      );
+      assert.isAbove(Number(referrerAccount.amount), 0);
+    });
+
+    it("Should reject fee distributions that do not sum to 100%", async () => {
+      try {
+        await program.methods
+          .configureFeeDistribution([
+            { wallet: authority.publicKey, shareBps: 6000 },
+            { wallet: user.publicKey, shareBps: 3000 },
+          ])
+          .accounts({ pool, authority: authority.publicKey })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have failed with invalid fee config");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFeeConfig");
+      }
+    });
+
+    it("Should require the split once a distribution exists", async () => {
+      await program.methods
+        .configureFeeDistribution([
+          { wallet: authority.publicKey, shareBps: 7000 },
+          { wallet: user.publicKey, shareBps: 3000 },
+        ])
+        .accounts({ pool, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      try {
+        await program.methods
+          .withdrawProtocolFees(new anchor.BN(0))
+          .accounts({ pool, authority: authority.publicKey })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have failed without the fee recipients");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFeeRecipient");
+      }
+
+      await program.methods
+        .withdrawProtocolFees(new anchor.BN(0))
+        .accounts({ pool, authority: authority.publicKey })
+        .remainingAccounts([
+          { pubkey: authority.publicKey, isSigner: false, isWritable: true },
+          { pubkey: user.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([authority])
+        .rpc();
+    });
+  });
+
+  describe("9. Accounting Reconciliation", () => {
+    it("Should verify pool balances on-chain and client-side", async () => {
+      const report = await verifyPool(program, pool);
+      assert.isTrue(report.ok);
+
+      const surplus = await program.methods
+        .verifyPool()
+        .accounts({ pool })
+        .remainingAccounts(report.remainingAccounts)
+        .view();
+      assert.equal(surplus.toNumber(), report.surplus);
+    });
+
+    it("Should reject an incomplete stake account set", async () => {
+      const report = await verifyPool(program, pool);
+      if (report.stakeAccounts.length === 0) return;
+
+      try {
+        await program.methods
+          .verifyPool()
+          .accounts({ pool })
+          .remainingAccounts(report.remainingAccounts.slice(2))
+          .rpc();
+        assert.fail("verify_pool accepted a partial set");
+      } catch (err) {
+        assert.include(err.toString(), "IncompleteStakeAccountSet");
+      }
+    });
+  });
+
+  describe("10. Validator Commission Monitoring", () => {
+    it("Should record the commission at admission", async () => {
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+
+      const validatorAccount = await program.account.validatorInfo.fetch(
+        validatorInfo,
+      );
+      assert.equal(
+        validatorAccount.admissionCommission,
+        validatorAccount.commission,
+      );
+      assert.equal(validatorAccount.quarantineEpoch.toNumber(), 0);
+    });
+
+    it("Should keep a validator active while its commission is unchanged", async () => {
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+
+      await program.methods
+        .checkValidatorCommission()
+        .accounts({
+          pool: pool,
+          validatorInfo,
+          validatorVoteAccount: validatorVoteAccount,
+        })
+        .rpc();
+
+      const validatorAccount = await program.account.validatorInfo.fetch(
+        validatorInfo,
+      );
+      assert.equal(validatorAccount.isActive, true);
+    });
+
+    it("Should reject unauthorized max commission changes", async () => {
+      try {
+        await program.methods
+          .configureMaxCommission(50)
+          .accounts({ pool, authority: user.publicKey })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (err) {
+        assert.include(err.toString(), "Unauthorized");
+      }
+    });
+  });
+
+  describe("11. Validator Admission", () => {
+    it("Should reject accounts that are not vote accounts", async () => {
+      const notAVoteAccount = Keypair.generate().publicKey;
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([1])],
+        program.programId,
+      );
+
+      try {
+        await program.methods
+          .addValidator(notAVoteAccount, 10)
+          .accounts({
+            authority: authority.publicKey,
+            pool: pool,
+            validatorInfo,
+            voteAccount: notAVoteAccount,
+          })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have failed with invalid vote account error");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidVoteAccount");
+      }
+    });
+
+    it("Should never re-add a blacklisted vote account", async () => {
+      const bannedVote = Keypair.generate().publicKey;
+      const [blacklist] = PublicKey.findProgramAddressSync(
+        [Buffer.from("blacklist"), pool.toBuffer()],
+        program.programId,
+      );
+
+      await program.methods
+        .blacklistValidator(bannedVote)
+        .accounts({ pool, authority: authority.publicKey, validatorInfo: null })
+        .signers([authority])
+        .rpc();
+
+      const blacklistAccount = await program.account.validatorBlacklist.fetch(
+        blacklist,
+      );
+      assert.equal(
+        blacklistAccount.voteAccounts[blacklistAccount.voteAccounts.length - 1].toString(),
+        bannedVote.toString(),
+      );
+
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([1])],
+        program.programId,
+      );
+
+      try {
+        await program.methods
+          .addValidator(bannedVote, 10)
+          .accounts({
+            authority: authority.publicKey,
+            pool: pool,
+            validatorInfo,
+            voteAccount: bannedVote,
+          })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have failed with blacklisted error");
+      } catch (err) {
+        assert.include(err.toString(), "ValidatorBlacklisted");
+      }
+    });
+  });
+
+  describe("12. Performance-Weighted Allocation", () => {
+    it("Should give the only active validator the whole target", async () => {
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+
+      await program.methods
+        .configureAllocationMode({ performanceWeighted: {} })
+        .accounts({ pool, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      await program.methods
+        .updateAllocations()
+        .accounts({ pool })
+        .remainingAccounts([
+          { pubkey: validatorInfo, isWritable: true, isSigner: false },
+        ])
+        .rpc();
+
+      const validatorAccount = await program.account.validatorInfo.fetch(
+        validatorInfo,
+      );
+      assert.equal(validatorAccount.targetAllocationBps, 10_000);
+
+      await program.methods
+        .configureAllocationMode({ static: {} })
+        .accounts({ pool, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    });
+
+    it("Should reject unauthorized allocation overrides", async () => {
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+
+      try {
+        await program.methods
+          .setValidatorAllocation(100, true)
+          .accounts({ pool, authority: user.publicKey, validatorInfo })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (err) {
+        assert.include(err.toString(), "Unauthorized");
+      }
+    });
+  });
+
+  describe("13. Stake Concentration Caps", () => {
+    it("Should store caps and validator tags", async () => {
+      const [validatorInfo] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), pool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+
+      await program.methods
+        .configureConcentrationCaps(2_000, 3_300)
+        .accounts({ pool, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      await program.methods
+        .setValidatorTag(7)
+        .accounts({ pool, authority: authority.publicKey, validatorInfo })
+        .signers([authority])
+        .rpc();
+
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      assert.equal(poolAccount.maxValidatorShareBps, 2_000);
+      assert.equal(poolAccount.maxTagShareBps, 3_300);
+
+      const validatorAccount = await program.account.validatorInfo.fetch(
+        validatorInfo,
+      );
+      assert.equal(validatorAccount.concentrationTag, 7);
+    });
+
+    it("Should reject unauthorized cap changes", async () => {
+      try {
+        await program.methods
+          .configureConcentrationCaps(10_000, 10_000)
+          .accounts({ pool, authority: user.publicKey })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (err) {
+        assert.include(err.toString(), "Unauthorized");
+      }
+    });
+  });
+
+  describe("14. Token-2022 Metadata Mint", () => {
+    const metadataMint = Keypair.generate();
+    let metadataPool: PublicKey;
+
+    before(() => {
+      [metadataPool] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool"), metadataMint.publicKey.toBuffer()],
+        program.programId,
+      );
+    });
+
+    it("Should create a pool whose fSOL mint carries metadata", async () => {
+      const uri = "https://fluidsol.example/fsol.json";
+
+      await program.methods
+        .initializePoolWithMetadata(uri)
+        .accounts({
+          authority: authority.publicKey,
+          pool: metadataPool,
+          fluidSolMint: metadataMint.publicKey,
+        })
+        .signers([authority, metadataMint])
+        .rpc();
+
+      const metadata = await getTokenMetadata(
+        provider.connection,
+        metadataMint.publicKey,
+      );
+      assert.equal(metadata.name, "FluidSOL");
+      assert.equal(metadata.symbol, "fSOL");
+      assert.equal(metadata.uri, uri);
+      assert.equal(metadata.updateAuthority.toString(), metadataPool.toString());
+    });
+
+    it("Should deposit into the Token-2022 pool", async () => {
+      const userAccount = await getAssociatedTokenAddress(
+        metadataMint.publicKey,
+        user.publicKey,
+        false,
+        TOKEN_2022_PROGRAM_ID,
+      );
+      await createAssociatedTokenAccount(
+        provider.connection,
+        user,
+        metadataMint.publicKey,
+        user.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID,
+      );
+
+      await program.methods
+        .depositSol(new anchor.BN(LAMPORTS_PER_SOL))
+        .accounts({
+          pool: metadataPool,
+          user: user.publicKey,
+          fluidSolMint: metadataMint.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          userFluidSolAccount: userAccount,
+        })
+        .signers([user])
+        .rpc();
+
+      const tokenAccount = await getTokenAccount(
+        provider.connection,
+        userAccount,
+        undefined,
+        TOKEN_2022_PROGRAM_ID,
+      );
+      assert.equal(tokenAccount.amount.toString(), LAMPORTS_PER_SOL.toString());
+    });
+  });
+
+  describe("15. Metaplex Metadata", () => {
+    const TOKEN_METADATA_PROGRAM_ID = new PublicKey(
+      "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+    );
+    let metadata: PublicKey;
+
+    before(() => {
+      [metadata] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("metadata"),
+          TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+          fluidSOLMint.publicKey.toBuffer(),
+        ],
+        TOKEN_METADATA_PROGRAM_ID,
+      );
+    });
+
+    it("Should create metadata for the classic fSOL mint", async () => {
+      await program.methods
+        .createTokenMetadata("https://fluidsol.example/fsol.json")
+        .accounts({
+          authority: authority.publicKey,
+          pool,
+          fluidSolMint: fluidSOLMint.publicKey,
+          metadata,
+        })
+        .signers([authority])
+        .rpc();
+
+      const account = await provider.connection.getAccountInfo(metadata);
+      assert.isTrue(account.owner.equals(TOKEN_METADATA_PROGRAM_ID));
+      assert.include(account.data.toString(), "FluidSOL");
+    });
+
+    it("Should update the metadata URI", async () => {
+      const uri = "https://fluidsol.example/fsol-v2.json";
+
+      await program.methods
+        .updateTokenMetadata(uri)
+        .accounts({ authority: authority.publicKey, pool, metadata })
+        .signers([authority])
+        .rpc();
+
+      const account = await provider.connection.getAccountInfo(metadata);
+      assert.include(account.data.toString(), uri);
+    });
+
+    it("Should reject metadata updates from non-authority", async () => {
+      try {
+        await program.methods
+          .updateTokenMetadata("https://evil.example")
+          .accounts({ authority: user.publicKey, pool, metadata })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (err) {
+        assert.include(err.toString(), "Unauthorized");
+      }
+    });
+  });
+
+  describe("16. Exchange-Rate History", () => {
+    let rateHistory: PublicKey;
+
+    before(() => {
+      [rateHistory] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rate_history"), pool.toBuffer()],
+        program.programId,
+      );
+    });
+
+    it("Should snapshot the exchange rate on reward updates", async () => {
+      await program.methods
+        .initializeRateHistory()
+        .accounts({ authority: authority.publicKey, pool })
+        .signers([authority])
+        .rpc();
+
+      await program.methods
+        .updateRewards(new anchor.BN(0.05 * LAMPORTS_PER_SOL))
+        .accounts({
+          pool,
+          authority: authority.publicKey,
+          rateHistory,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      const history = await program.account.rateHistory.fetch(rateHistory);
+      const latest = history.entries[history.head];
+
+      // Both snapshots fall in the same epoch, so the second replaced the first
+      assert.equal(history.entries.length, 1);
+      assert.equal(
+        latest.exchangeRate.toString(),
+        poolAccount.exchangeRate.toString(),
+      );
+      assert.equal(
+        latest.supply.toString(),
+        poolAccount.totalFluidSolMinted.toString(),
+      );
+    });
+
+    it("Should need more than one epoch of history for APY", async () => {
+      try {
+        await program.methods.getApy(new anchor.BN(7)).accounts({ rateHistory }).view();
+
+        assert.fail("Should have failed with insufficient history");
+      } catch (err) {
+        assert.include(err.toString(), "InsufficientRateHistory");
+      }
+    });
+  });
+
+  describe("17. fSOL Price Oracle", () => {
+    let priceOracle: PublicKey;
+
+    before(() => {
+      [priceOracle] = PublicKey.findProgramAddressSync(
+        [Buffer.from("price_oracle"), pool.toBuffer()],
+        program.programId,
+      );
+    });
+
+    it("Should move the oracle with the exchange rate and its slot", async () => {
+      await program.methods
+        .initializePriceOracle()
+        .accounts({ authority: authority.publicKey, pool })
+        .signers([authority])
+        .rpc();
+
+      await program.methods
+        .updateRewards(new anchor.BN(0.05 * LAMPORTS_PER_SOL))
+        .accounts({
+          pool,
+          authority: authority.publicKey,
+          priceOracle,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      const oracle = await program.account.priceOracle.fetch(priceOracle);
+      assert.equal(
+        oracle.exchangeRate.toString(),
+        poolAccount.exchangeRate.toString(),
+      );
+      assert.equal(
+        oracle.lastUpdateSlot.toString(),
+        poolAccount.rateUpdateSlot.toString(),
+      );
+    });
+
+    it("Should return the pool's rate with the slot it was booked at", async () => {
+      // Book rewards without the oracle so the stored copy falls behind
+      await program.methods
+        .updateRewards(new anchor.BN(0.01 * LAMPORTS_PER_SOL))
+        .accounts({
+          pool,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      const rate = await program.methods
+        .getExchangeRate()
+        .accounts({ pool })
+        .view();
+      assert.equal(
+        rate.exchangeRate.toString(),
+        poolAccount.exchangeRate.toString(),
+      );
+      assert.equal(
+        rate.lastUpdateSlot.toString(),
+        poolAccount.rateUpdateSlot.toString(),
+      );
+      assert.isFalse(rate.isStale);
+
+      // The stored copy keeps its own rate and slot until refreshed
+      let oracle = await program.account.priceOracle.fetch(priceOracle);
+      assert.isBelow(
+        oracle.lastUpdateSlot.toNumber(),
+        poolAccount.rateUpdateSlot.toNumber(),
+      );
+
+      await program.methods
+        .refreshPriceOracle()
+        .accounts({ pool, priceOracle })
+        .rpc();
+
+      oracle = await program.account.priceOracle.fetch(priceOracle);
+      assert.equal(
+        oracle.exchangeRate.toString(),
+        poolAccount.exchangeRate.toString(),
+      );
+      assert.equal(
+        oracle.lastUpdateSlot.toString(),
+        poolAccount.rateUpdateSlot.toString(),
+      );
+    });
+
+    it("Should flag a rate older than the configured maximum", async () => {
+      await program.methods
+        .configurePriceOracle(new anchor.BN(1))
+        .accounts({ authority: authority.publicKey, pool })
+        .signers([authority])
+        .rpc();
+
+      // Let a few slots pass without booking the rate
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+
+      const rate = await program.methods
+        .getExchangeRate()
+        .accounts({ pool })
+        .view();
+      assert.isTrue(rate.isStale);
+
+      await program.methods
+        .configurePriceOracle(new anchor.BN(432_000))
+        .accounts({ authority: authority.publicKey, pool })
+        .signers([authority])
+        .rpc();
+
+      try {
+        await program.methods
+          .configurePriceOracle(new anchor.BN(0))
+          .accounts({ authority: authority.publicKey, pool })
+          .signers([authority])
+          .rpc();
+
+        assert.fail("Should have rejected a zero staleness limit");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidAmount");
+      }
+    });
+  });
+
+  describe("18. CPI Consumer", () => {
+    const consumer = anchor.workspace
+      .FluidsolConsumer as Program<FluidsolConsumer>;
+
+    it("Should deposit through another program and read the fSOL minted", async () => {
+      const poolBefore = await program.account.stakingPool.fetch(pool);
+      const balanceBefore = await getTokenAccount(
+        provider.connection,
+        userFluidSOLAccount,
+      );
+      const depositAmount = LAMPORTS_PER_SOL;
+
+      await consumer.methods
+        .depositViaCpi(new anchor.BN(depositAmount), new anchor.BN(1))
+        .accounts({
+          user: user.publicKey,
+          pool,
+          fluidSolMint: fluidSOLMint.publicKey,
+          userFluidSolAccount: userFluidSOLAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      const poolAfter = await program.account.stakingPool.fetch(pool);
+      const balanceAfter = await getTokenAccount(
+        provider.connection,
+        userFluidSOLAccount,
+      );
+      assert.equal(
+        poolAfter.totalSolDeposited.toNumber(),
+        poolBefore.totalSolDeposited.toNumber() + depositAmount,
+      );
+      assert.isAbove(Number(balanceAfter.amount), Number(balanceBefore.amount));
+    });
+
+    it("Should enforce the caller's minimum on the returned amount", async () => {
+      try {
+        await consumer.methods
+          .depositViaCpi(
+            new anchor.BN(LAMPORTS_PER_SOL),
+            new anchor.BN(10 * LAMPORTS_PER_SOL),
+          )
+          .accounts({
+            user: user.publicKey,
+            pool,
+            fluidSolMint: fluidSOLMint.publicKey,
+            userFluidSolAccount: userFluidSOLAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with slippage error");
+      } catch (err) {
+        assert.include(err.toString(), "SlippageExceeded");
+      }
+    });
+  });
+
+  describe("19. SPL Stake-Pool Compatibility", () => {
+    // The manager fee slot must be the pool's fee token account
+    let managerFeeAccount: PublicKey;
+
+    before(async () => {
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      managerFeeAccount = poolAccount.feeTokenAccount;
+    });
+
+    // SPL DepositSol: tag 14 followed by the lamports as a little-endian u64
+    const splDepositSol = (lamports: number) => {
+      const data = Buffer.alloc(9);
+      data.writeUInt8(14, 0);
+      data.writeBigUInt64LE(BigInt(lamports), 1);
+      return new anchor.web3.TransactionInstruction({
+        programId: program.programId,
+        keys: [
+          { pubkey: pool, isSigner: false, isWritable: true },
+          { pubkey: pool, isSigner: false, isWritable: true },
+          { pubkey: pool, isSigner: false, isWritable: true },
+          { pubkey: user.publicKey, isSigner: true, isWritable: true },
+          { pubkey: userFluidSOLAccount, isSigner: false, isWritable: true },
+          { pubkey: managerFeeAccount, isSigner: false, isWritable: true },
+          { pubkey: userFluidSOLAccount, isSigner: false, isWritable: true },
+          { pubkey: fluidSOLMint.publicKey, isSigner: false, isWritable: true },
+          {
+            pubkey: anchor.web3.SystemProgram.programId,
+            isSigner: false,
+            isWritable: false,
+          },
+          { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        ],
+        data,
+      });
+    };
+
+    it("Should reject SPL instructions until the authority opts in", async () => {
+      try {
+        await anchor.web3.sendAndConfirmTransaction(
+          provider.connection,
+          new anchor.web3.Transaction().add(splDepositSol(LAMPORTS_PER_SOL)),
+          [user],
+        );
+
+        assert.fail("Should have failed with SPL compat disabled");
+      } catch (err) {
+        const logs = (err.logs ?? []).join("\n");
+        assert.include(logs, "SplCompatDisabled");
+      }
+    });
+
+    it("Should accept an SPL DepositSol once enabled", async () => {
+      await program.methods
+        .configureSplCompat(true)
+        .accounts({ authority: authority.publicKey, pool })
+        .signers([authority])
+        .rpc();
+
+      const poolBefore = await program.account.stakingPool.fetch(pool);
+      const balanceBefore = await getTokenAccount(
+        provider.connection,
+        userFluidSOLAccount,
+      );
+      const depositAmount = LAMPORTS_PER_SOL;
+
+      await anchor.web3.sendAndConfirmTransaction(
+        provider.connection,
+        new anchor.web3.Transaction().add(splDepositSol(depositAmount)),
+        [user],
+      );
+
+      const poolAfter = await program.account.stakingPool.fetch(pool);
+      const balanceAfter = await getTokenAccount(
+        provider.connection,
+        userFluidSOLAccount,
+      );
+      assert.isTrue(poolAfter.splCompatEnabled);
+      assert.equal(
+        poolAfter.totalSolDeposited.toNumber(),
+        poolBefore.totalSolDeposited.toNumber() + depositAmount,
+      );
+      assert.isAbove(Number(balanceAfter.amount), Number(balanceBefore.amount));
+    });
+  });
+
+  describe("20. Liquid Unstake Pool", () => {
+    const liquidityProvider = Keypair.generate();
+    const targetLiquidity = 2 * LAMPORTS_PER_SOL;
+    let unstakePool: PublicKey;
+    let lpMint: PublicKey;
+    let fluidSOLVault: PublicKey;
+    let providerLpAccount: PublicKey;
+
+    // Swap and return the fee paid with the SOL value of the fSOL
+    const swap = async (fluidSOLAmount: number) => {
+      const poolAccount = await program.account.stakingPool.fetch(pool);
+      const before = await program.account.unstakePool.fetch(unstakePool);
+      await program.methods
+        .liquidUnstake(new anchor.BN(fluidSOLAmount), new anchor.BN(1))
+        .accounts({
+          user: user.publicKey,
+          pool,
+          fluidSolMint: fluidSOLMint.publicKey,
+          userFluidSolAccount: userFluidSOLAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+      const after = await program.account.unstakePool.fetch(unstakePool);
+
+      const solValue = new anchor.BN(fluidSOLAmount)
+        .mul(poolAccount.totalSolDeposited)
+        .div(poolAccount.totalFluidSolMinted);
+      const fee = after.totalFeesEarned.sub(before.totalFeesEarned);
+      assert.equal(
+        before.solLiquidity.sub(after.solLiquidity).toString(),
+        solValue.sub(fee).toString(),
+      );
+      return { fee, solValue };
+    };
+
+    before(async () => {
+      [unstakePool] = PublicKey.findProgramAddressSync(
+        [Buffer.from("unstake_pool"), pool.toBuffer()],
+        program.programId,
+      );
+      [lpMint] = PublicKey.findProgramAddressSync(
+        [Buffer.from("lp_mint"), unstakePool.toBuffer()],
+        program.programId,
+      );
+      [fluidSOLVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("unstake_vault"), unstakePool.toBuffer()],
+        program.programId,
+      );
+
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(
+          liquidityProvider.publicKey,
+          5 * LAMPORTS_PER_SOL,
+        ),
+      );
+    });
+
+    // Left behind by the first deposit so the share price can't be inflated
+    const lockedLpShares = 1_000_000;
+
+    it("Should mint LP shares 1:1 for the first liquidity, minus the locked shares", async () => {
+      await program.methods
+        .initializeUnstakePool(30, 300, new anchor.BN(targetLiquidity))
+        .accounts({
+          authority: authority.publicKey,
+          pool,
+          fluidSolMint: fluidSOLMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority])
+        .rpc();
+
+      providerLpAccount = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        lpMint,
+        liquidityProvider.publicKey,
+      );
+
+      await program.methods
+        .addUnstakeLiquidity(new anchor.BN(3 * LAMPORTS_PER_SOL))
+        .accounts({
+          provider: liquidityProvider.publicKey,
+          pool,
+          providerLpAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const lpAccount = await getTokenAccount(
+        provider.connection,
+        providerLpAccount,
+      );
+      const unstakePoolAccount =
+        await program.account.unstakePool.fetch(unstakePool);
+      assert.equal(
+        lpAccount.amount.toString(),
+        (3 * LAMPORTS_PER_SOL - lockedLpShares).toString(),
+      );
+      assert.equal(unstakePoolAccount.lockedLp.toNumber(), lockedLpShares);
+      assert.equal(
+        unstakePoolAccount.solLiquidity.toNumber(),
+        3 * LAMPORTS_PER_SOL,
+      );
+    });
+
+    it("Should charge the minimum fee while liquidity stays above target", async () => {
+      const { fee, solValue } = await swap(0.1 * LAMPORTS_PER_SOL);
+      assert.equal(fee.toString(), solValue.muln(30).divn(10_000).toString());
+
+      const vault = await getTokenAccount(provider.connection, fluidSOLVault);
+      const unstakePoolAccount =
+        await program.account.unstakePool.fetch(unstakePool);
+      assert.equal(
+        vault.amount.toString(),
+        (0.1 * LAMPORTS_PER_SOL).toString(),
+      );
+      assert.equal(
+        unstakePoolAccount.vaultFluidSol.toString(),
+        vault.amount.toString(),
+      );
+    });
+
+    it("Should charge more as a swap drains liquidity", async () => {
+      const { fee, solValue } = await swap(1.2 * LAMPORTS_PER_SOL);
+      assert.isTrue(fee.gt(solValue.muln(30).divn(10_000)));
+      assert.isTrue(fee.lt(solValue.muln(300).divn(10_000)));
+    });
+
+    it("Should enforce the minimum SOL out", async () => {
+      try {
+        await program.methods
+          .liquidUnstake(
+            new anchor.BN(0.1 * LAMPORTS_PER_SOL),
+            new anchor.BN(LAMPORTS_PER_SOL),
+          )
+          .accounts({
+            user: user.publicKey,
+            pool,
+            fluidSolMint: fluidSOLMint.publicKey,
+            userFluidSolAccount: userFluidSOLAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([user])
+          .rpc();
+
+        assert.fail("Should have failed with slippage error");
+      } catch (err) {
+        assert.include(err.toString(), "SlippageExceeded");
+      }
+    });
+
+    it("Should pay LPs their share including swap fees", async () => {
+      const balanceBefore = await provider.connection.getBalance(
+        liquidityProvider.publicKey,
+      );
+
+      await program.methods
+        .removeUnstakeLiquidity(new anchor.BN(LAMPORTS_PER_SOL))
+        .accounts({
+          provider: liquidityProvider.publicKey,
+          pool,
+          providerLpAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const balanceAfter = await provider.connection.getBalance(
+        liquidityProvider.publicKey,
+      );
+      const lpAccount = await getTokenAccount(
+        provider.connection,
+        providerLpAccount,
+      );
+      assert.equal(
+        lpAccount.amount.toString(),
+        (2 * LAMPORTS_PER_SOL - lockedLpShares).toString(),
+      );
+      // 1/3 of the pool is worth more than the 1 SOL put in (minus tx fee)
+      assert.isAbove(balanceAfter - balanceBefore, LAMPORTS_PER_SOL - 10_000);
+    });
+  });
+
+  describe("21. Stake Account Withdrawal", () => {
+    const stakeMint = Keypair.generate();
+    const depositor = Keypair.generate();
+    const stakeAmount = LAMPORTS_PER_SOL;
+    const rewards = 0.1 * LAMPORTS_PER_SOL;
+    let stakePool: PublicKey;
+    let stakeValidator: PublicKey;
+    let stakeAccount: PublicKey;
+    let stakeRecord: PublicKey;
+
+    before(async () => {
+      [stakePool] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool"), stakeMint.publicKey.toBuffer()],
+        program.programId,
+      );
+      [stakeValidator] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), stakePool.toBuffer(), Buffer.from([0])],
+        program.programId,
+      );
+      [stakeAccount] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("stake"),
+          stakePool.toBuffer(),
+          validatorVoteAccount.toBuffer(),
+          new anchor.BN(0).toArrayLike(Buffer, "le", 4),
+        ],
+        program.programId,
+      );
+      [stakeRecord] = PublicKey.findProgramAddressSync(
+        [Buffer.from("stake_record"), stakeAccount.toBuffer()],
+        program.programId,
+      );
+
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(
+          depositor.publicKey,
+          3 * LAMPORTS_PER_SOL,
+        ),
+      );
+
+      // Fresh pool keeping reward fees as lamports
+      await program.methods
+        .initializePool()
+        .accounts({
+          authority: authority.publicKey,
+          fluidSolMint: stakeMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority, stakeMint])
+        .rpc();
+      const depositorAccount = await createAssociatedTokenAccount(
+        provider.connection,
+        depositor,
+        stakeMint.publicKey,
+        depositor.publicKey,
+      );
+      await program.methods
+        .depositSol(new anchor.BN(2 * LAMPORTS_PER_SOL))
+        .accounts({
+          pool: stakePool,
+          user: depositor.publicKey,
+          fluidSolMint: stakeMint.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          userFluidSolAccount: depositorAccount,
+        })
+        .signers([depositor])
+        .rpc();
+      await program.methods
+        .addValidator(validatorVoteAccount, 100)
+        .accounts({
+          authority: authority.publicKey,
+          pool: stakePool,
+          validatorInfo: stakeValidator,
+          voteAccount: validatorVoteAccount,
+        })
+        .signers([authority])
+        .rpc();
+    });
+
+    it("Should return staked SOL to the reserve and keep harvested fees booked", async function () {
+      this.timeout(600000);
+      const poolBefore = await program.account.stakingPool.fetch(stakePool);
+
+      await program.methods
+        .stakeToValidator(new anchor.BN(stakeAmount))
+        .accounts({
+          pool: stakePool,
+          authority: authority.publicKey,
+          validatorInfo: stakeValidator,
+          stakeAccount,
+          validatorVoteAccount,
+          stakeHistory: SYSVAR_STAKE_HISTORY_PUBKEY,
+          stakeConfig: new PublicKey(
+            "StakeConfig11111111111111111111111111111111",
+          ),
+        })
+        .signers([authority])
+        .rpc();
+
+      // Lamports landing on the stake account are harvested like rewards
+      await anchor.web3.sendAndConfirmTransaction(
+        provider.connection,
+        new anchor.web3.Transaction().add(
+          anchor.web3.SystemProgram.transfer({
+            fromPubkey: authority.publicKey,
+            toPubkey: stakeAccount,
+            lamports: rewards,
+          }),
+        ),
+        [authority],
+      );
+      await program.methods
+        .harvestRewards(0)
+        .accounts({
+          authority: authority.publicKey,
+          pool: stakePool,
+          validatorInfo: stakeValidator,
+          stakeRecord,
+          stakeAccount,
+          fluidSolMint: null,
+          feeTokenAccount: null,
+          tokenProgram: null,
+          rateHistory: null,
+          priceOracle: null,
+        })
+        .signers([authority])
+        .rpc();
+
+      const harvested = await program.account.stakingPool.fetch(stakePool);
+      const fee = harvested.protocolFeesEarned
+        .sub(poolBefore.protocolFeesEarned)
+        .toNumber();
+      assert.equal(fee, rewards / 10);
+      const harvestedRecord = await program.account.poolStakeAccount.fetch(
+        stakeRecord,
+      );
+      assert.equal(harvestedRecord.feeLamports.toNumber(), fee);
+
+      await program.methods
+        .deactivateStakeAccount()
+        .accounts({
+          authority: authority.publicKey,
+          pool: stakePool,
+          validatorInfo: stakeValidator,
+          stakeRecord,
+          stakeAccount,
+        })
+        .signers([authority])
+        .rpc();
+
+      // Stake that activated before deactivating needs an epoch to cool down
+      const record = await program.account.poolStakeAccount.fetch(stakeRecord);
+      if (!record.activationEpoch.eq(record.deactivationEpoch)) {
+        const cooldownEpoch = record.deactivationEpoch.toNumber();
+        while ((await provider.connection.getEpochInfo()).epoch <= cooldownEpoch) {
+          await new Promise((resolve) => setTimeout(resolve, 2000));
+        }
+      }
+
+      await program.methods
+        .withdrawStakeAccount()
+        .accounts({
+          authority: authority.publicKey,
+          pool: stakePool,
+          validatorInfo: stakeValidator,
+          stakeRecord,
+          stakeAccount,
+          stakeHistory: SYSVAR_STAKE_HISTORY_PUBKEY,
+        })
+        .signers([authority])
+        .rpc();
+
+      const poolAfter = await program.account.stakingPool.fetch(stakePool);
+      assert.equal(poolAfter.stakedSolBalance.toNumber(), 0);
+      assert.equal(poolAfter.stakeAccountCount, 0);
+      assert.equal(
+        poolAfter.liquidReserve.toNumber(),
+        poolBefore.liquidReserve.toNumber() + rewards - fee,
+      );
+      assert.equal(
+        poolAfter.protocolFeesEarned.toNumber(),
+        poolBefore.protocolFeesEarned.toNumber() + fee,
+      );
+
+      const report = await verifyPool(program, stakePool);
+      assert.isTrue(report.ok);
+      assert.equal(report.surplus, 0);
+    });
+  });
 });
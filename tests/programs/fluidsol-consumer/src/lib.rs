@@ -0,0 +1,80 @@
+// Only for `#[program]`'s generated IDL helpers (crate-root `__private::__idl`),
+// which call the deprecated AccountInfo::realloc and can't take an item-level allow
+#![allow(deprecated)]
+
+// Example of a program composing with FluidSOL: deposits the caller's SOL
+// through the interface crate and checks the fSOL minted (read from return
+// data) against a minimum, the way a vault or router would.
+
+use anchor_lang::prelude::*;
+use fluidsol_interface::LiquidStaking;
+
+declare_id!("9VA3yU3ar2CahZEmwFcUPSXJzLG2BhhAa5r4HymZGwyb");
+
+#[program]
+pub mod fluidsol_consumer {
+    use super::*;
+
+    /// Deposit `sol_amount` into a FluidSOL pool, failing if it mints less than `min_fluid_sol`
+    pub fn deposit_via_cpi(
+        ctx: Context<DepositViaCpi>,
+        sol_amount: u64,
+        min_fluid_sol: u64,
+    ) -> Result<()> {
+        let minted = fluidsol_interface::cpi::deposit_sol(
+            CpiContext::new(
+                ctx.accounts.fluidsol_program.to_account_info(),
+                fluidsol_interface::accounts::DepositSol {
+                    user: ctx.accounts.user.to_account_info(),
+                    pool: ctx.accounts.pool.to_account_info(),
+                    fluid_sol_mint: ctx.accounts.fluid_sol_mint.to_account_info(),
+                    user_fluid_sol_account: ctx.accounts.user_fluid_sol_account.to_account_info(),
+                    fee_token_account: None,
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ),
+            sol_amount,
+        )?
+        .get();
+
+        require!(minted >= min_fluid_sol, ConsumerError::SlippageExceeded);
+
+        msg!("Deposited {} SOL via CPI for {} fSOL",
+             sol_amount as f64 / 1_000_000_000.0,
+             minted as f64 / 1_000_000_000.0);
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct DepositViaCpi<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Validated by FluidSOL
+    #[account(mut)]
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by FluidSOL
+    #[account(mut)]
+    pub fluid_sol_mint: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the token program
+    #[account(mut)]
+    pub user_fluid_sol_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Validated by FluidSOL against the mint
+    pub token_program: UncheckedAccount<'info>,
+
+    pub fluidsol_program: Program<'info, LiquidStaking>,
+}
+
+#[error_code]
+pub enum ConsumerError {
+    #[msg("Deposit minted less fSOL than the minimum")]
+    SlippageExceeded,
+}
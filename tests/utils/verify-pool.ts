@@ -0,0 +1,83 @@
+import { Program } from "@coral-xyz/anchor";
+import { AccountMeta, PublicKey } from "@solana/web3.js";
+import { LiquidStaking } from "../../target/types/liquid_staking";
+
+// Offset of PoolStakeAccount.pool (right after the 8 byte discriminator)
+const STAKE_RECORD_POOL_OFFSET = 8;
+
+export interface PoolReconciliation {
+  poolBalance: number; // Pool PDA lamports minus rent
+  stakeBalance: number; // Stake account lamports minus rent reserves
+  liquidReserve: number;
+  stakedSolBalance: number;
+  protocolFeesEarned: number;
+  actualTotal: number;
+  recordedTotal: number;
+  surplus: number; // What verify_pool returns (e.g. unharvested rewards)
+  stakeAccounts: PublicKey[];
+  remainingAccounts: AccountMeta[]; // (stake_record, stake_account) pairs for verify_pool
+  ok: boolean;
+}
+
+/**
+ * Client-side equivalent of the on-chain `verify_pool` instruction.
+ * Finds every PoolStakeAccount record of the pool and checks that actual
+ * lamports cover the pool's recorded balances.
+ */
+export async function verifyPool(
+  program: Program<LiquidStaking>,
+  pool: PublicKey,
+): Promise<PoolReconciliation> {
+  const connection = program.provider.connection;
+  const poolAccount = await program.account.stakingPool.fetch(pool);
+  const poolInfo = await connection.getAccountInfo(pool);
+  const poolRent = await connection.getMinimumBalanceForRentExemption(
+    poolInfo.data.length,
+  );
+
+  const records = await program.account.poolStakeAccount.all([
+    {
+      memcmp: {
+        offset: STAKE_RECORD_POOL_OFFSET,
+        bytes: pool.toBase58(),
+      },
+    },
+  ]);
+  const stakeAccounts = records.map(({ account }) => account.stakeAccount);
+
+  let stakeBalance = 0;
+  for (const stakeAccount of stakeAccounts) {
+    const info = await connection.getParsedAccountInfo(stakeAccount);
+    const parsed = (info.value.data as any).parsed;
+    const rentReserve = Number(parsed.info.meta.rentExemptReserve);
+    stakeBalance += info.value.lamports - rentReserve;
+  }
+
+  const poolBalance = poolInfo.lamports - poolRent;
+  const liquidReserve = poolAccount.liquidReserve.toNumber();
+  const stakedSolBalance = poolAccount.stakedSolBalance.toNumber();
+  const protocolFeesEarned = poolAccount.protocolFeesEarned.toNumber();
+  // Legacy stake not registered yet has no record to be counted by
+  const legacyStake = poolAccount.legacyStake.toNumber();
+  const actualTotal = poolBalance + stakeBalance;
+  const recordedTotal = liquidReserve + stakedSolBalance - legacyStake + protocolFeesEarned;
+
+  return {
+    poolBalance,
+    stakeBalance,
+    liquidReserve,
+    stakedSolBalance,
+    protocolFeesEarned,
+    actualTotal,
+    recordedTotal,
+    surplus: Math.max(actualTotal - recordedTotal, 0),
+    stakeAccounts,
+    remainingAccounts: records.flatMap(({ publicKey, account }) => [
+      { pubkey: publicKey, isWritable: false, isSigner: false },
+      { pubkey: account.stakeAccount, isWritable: false, isSigner: false },
+    ]),
+    ok:
+      actualTotal >= recordedTotal &&
+      records.length === poolAccount.stakeAccountCount,
+  };
+}